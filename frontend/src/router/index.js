@@ -1,6 +1,7 @@
 import { createRouter, createWebHistory } from 'vue-router'
 import Dashboard from '../views/Dashboard.vue'
 import TargetDetails from '../views/TargetDetails.vue'
+import LivePing from '../views/LivePing.vue'
 
 const routes = [
   {
@@ -13,6 +14,11 @@ const routes = [
     name: 'TargetDetails',
     component: TargetDetails,
     props: true
+  },
+  {
+    path: '/live',
+    name: 'LivePing',
+    component: LivePing
   }
 ]
 
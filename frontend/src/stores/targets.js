@@ -4,14 +4,26 @@ import axios from 'axios'
 export const useTargetsStore = defineStore('targets', {
   state: () => ({
     targets: [],
+    groups: [],
+    profiles: [],
     selectedTarget: null,
-    probeData: []
+    probeData: [],
+    maintenanceWindows: [],
+    revisions: []
   }),
   actions: {
     async fetchTargets() {
       const response = await axios.get('/api/targets')
       this.targets = response.data
     },
+    async fetchGroups() {
+      const response = await axios.get('/api/groups')
+      this.groups = response.data
+    },
+    async fetchProfiles() {
+      const response = await axios.get('/api/profiles')
+      this.profiles = response.data
+    },
     async fetchTargetDetails(id) {
       const response = await axios.get(`/api/targets/${id}`)
       this.selectedTarget = response.data
@@ -19,6 +31,18 @@ export const useTargetsStore = defineStore('targets', {
     async fetchProbeData(id, startTime, endTime) {
       const response = await axios.get(`/api/targets/${id}/data?start_time=${startTime}&end_time=${endTime}`)
       this.probeData = response.data
+    },
+    async fetchMaintenanceWindows(id, startTime, endTime) {
+      const response = await axios.get(`/api/maintenance?target_id=${id}&start_time=${startTime}&end_time=${endTime}`)
+      this.maintenanceWindows = response.data
+    },
+    async fetchRevisions(id) {
+      const response = await axios.get(`/api/targets/${id}/revisions`)
+      this.revisions = response.data
+    },
+    async probeTarget(id) {
+      const response = await axios.post(`/api/targets/${id}/probe`)
+      return response.data
     }
   }
 })
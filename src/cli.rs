@@ -0,0 +1,122 @@
+//! Command line subcommands, run instead of the server when given.
+
+use crate::database;
+use crate::migration::{Migrator, MigratorTrait};
+
+const USAGE: &str = "Usage: smokeping-rs [import-smokeping <config-file> [--dry-run] | migrate <up [n] | down [n] | status>]";
+
+/// Runs the subcommand in `args` (without the program name) and returns the exit code.
+pub async fn run(args: &[String]) -> i32 {
+    match args.first().map(String::as_str) {
+        Some("import-smokeping") => import_smokeping(&args[1..]).await,
+        Some("migrate") => migrate(&args[1..]).await,
+        Some("help") | Some("--help") | Some("-h") => {
+            println!("{}", USAGE);
+            0
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            2
+        }
+    }
+}
+
+async fn import_smokeping(args: &[String]) -> i32 {
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    let Some(path) = args.iter().find(|arg| !arg.starts_with("--")) else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("Failed to read '{}': {}", path, e);
+            return 1;
+        }
+    };
+    let db = match database::setup_database().await {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Failed to setup database: {:?}", e);
+            return 1;
+        }
+    };
+
+    match crate::smokeping_import::import(&db, &text, dry_run, "cli").await {
+        Ok(report) => {
+            let verb = if dry_run { "Would create" } else { "Created" };
+            for group in &report.groups_created {
+                println!("{} group {}", verb, group);
+            }
+            for target in &report.created {
+                let group = target.group.as_deref().map(|group| format!("{} / ", group)).unwrap_or_default();
+                println!("{} {}{} ({} {}, every {}s)", verb, group, target.name, target.probe_type, target.host, target.probe_interval_secs);
+            }
+            for skipped in &report.skipped {
+                println!("Skipped {}: {}", skipped.path, skipped.reason);
+            }
+            for unsupported in &report.unsupported {
+                println!("Unsupported at line {} ({}): {}", unsupported.line, unsupported.section, unsupported.directive);
+            }
+            println!(
+                "{} groups and {} targets {}, {} skipped, {} unsupported directives",
+                report.groups_created.len(),
+                report.created.len(),
+                if dry_run { "to create" } else { "created" },
+                report.skipped.len(),
+                report.unsupported.len()
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("Failed to import targets: {:?}", e);
+            1
+        }
+    }
+}
+
+// Without a step count `up` applies everything pending and `down` reverts the latest migration
+async fn migrate(args: &[String]) -> i32 {
+    let steps = match args.get(1).map(|steps| steps.parse::<u32>()) {
+        Some(Ok(steps)) => Some(steps),
+        Some(Err(_)) => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+        None => None,
+    };
+    let db = match database::connect().await {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Failed to connect to database: {:?}", e);
+            return 1;
+        }
+    };
+
+    let result = match args.first().map(String::as_str) {
+        Some("up") => Migrator::up(&db, steps).await,
+        Some("down") => Migrator::down(&db, Some(steps.unwrap_or(1))).await,
+        Some("status") => match Migrator::get_migration_with_status(&db).await {
+            Ok(migrations) => {
+                for migration in migrations {
+                    println!("{:<8} {}", migration.status().to_string(), migration.name());
+                }
+                Ok(())
+            }
+            Err(e) => Err(e),
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Migration failed: {:?}", e);
+            1
+        }
+    }
+}
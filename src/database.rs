@@ -1,18 +1,25 @@
-use sea_orm::{Database, DatabaseConnection, Schema, ConnectionTrait};
-use crate::models::target;
+use sea_orm::{Database, DatabaseConnection};
+use crate::migration::{Migrator, MigratorTrait};
 use std::path::Path;
 
+/// Connects to the database and applies pending migrations.
 pub async fn setup_database() -> Result<DatabaseConnection, sea_orm::DbErr> {
+    let db = connect().await?;
+    Migrator::up(&db, None).await?;
+    Ok(db)
+}
+
+/// Connects to `DATABASE_URL` without touching the schema.
+pub async fn connect() -> Result<DatabaseConnection, sea_orm::DbErr> {
     let db_url = std::env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite::memory:".to_string());
 
-    println!("Database URL: {}", db_url);
+    println!("Database URL: {}", redacted(&db_url));
+    check_backend(&db_url)?;
 
     // If it's a file-based SQLite database, ensure the directory exists and create the file
     if db_url.starts_with("sqlite:") && !db_url.contains(":memory:") {
         // Handle both sqlite:// and sqlite:/// formats
-        let path_str = if db_url.starts_with("sqlite:///") {
-            db_url.strip_prefix("sqlite://").unwrap()
-        } else if db_url.starts_with("sqlite://") {
+        let path_str = if db_url.starts_with("sqlite://") {
             db_url.strip_prefix("sqlite://").unwrap()
         } else {
             db_url.strip_prefix("sqlite:").unwrap()
@@ -40,12 +47,62 @@ pub async fn setup_database() -> Result<DatabaseConnection, sea_orm::DbErr> {
         }
     }
 
-    let db = Database::connect(&db_url).await?;
+    Database::connect(&db_url).await
+}
+
+// Fails early with the cargo feature to enable instead of sqlx's generic driver error
+fn check_backend(db_url: &str) -> Result<(), sea_orm::DbErr> {
+    let (backend, enabled) = match db_url.split_once(':').map(|(scheme, _)| scheme) {
+        Some("sqlite") => ("sqlite", cfg!(feature = "sqlite")),
+        Some("postgres") | Some("postgresql") => ("postgres", cfg!(feature = "postgres")),
+        Some("mysql") => ("mysql", cfg!(feature = "mysql")),
+        _ => {
+            return Err(sea_orm::DbErr::Custom(
+                "DATABASE_URL must start with sqlite:, postgres:// or mysql://".to_string(),
+            ))
+        }
+    };
+    if !enabled {
+        return Err(sea_orm::DbErr::Custom(format!(
+            "DATABASE_URL uses {} but smokeping-rs was built without the '{}' feature",
+            backend, backend
+        )));
+    }
+    Ok(())
+}
+
+// `db_url` with the password of its userinfo masked, for logging
+fn redacted(db_url: &str) -> String {
+    let Some((scheme, rest)) = db_url.split_once("://") else {
+        return db_url.to_string();
+    };
+    let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+    match rest[..authority_end].rsplit_once('@') {
+        Some((userinfo, host)) => match userinfo.split_once(':') {
+            Some((user, _)) => format!("{}://{}:***@{}{}", scheme, user, host, &rest[authority_end..]),
+            None => db_url.to_string(),
+        },
+        None => db_url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // Create table if it doesn't exist
-    let builder = db.get_database_backend();
-    let schema = Schema::new(builder);
-    db.execute(builder.build(schema.create_table_from_entity(target::Entity).if_not_exists())).await?;
+    #[test]
+    fn passwords_are_masked() {
+        assert_eq!(redacted("postgres://smoke:s3cret@db:5432/smokeping"), "postgres://smoke:***@db:5432/smokeping");
+        // An '@' in the password or path does not leak the rest of it
+        assert_eq!(redacted("mysql://smoke:p@ss@db/smokeping?ssl-mode=required"), "mysql://smoke:***@db/smokeping?ssl-mode=required");
+        assert_eq!(redacted("postgres://smoke:s3cret@db/path@x"), "postgres://smoke:***@db/path@x");
+    }
 
-    Ok(db)
+    #[test]
+    fn urls_without_a_password_are_unchanged() {
+        assert_eq!(redacted("postgres://smoke@db/smokeping"), "postgres://smoke@db/smokeping");
+        assert_eq!(redacted("postgres://db/smokeping"), "postgres://db/smokeping");
+        assert_eq!(redacted("sqlite:///var/lib/smokeping/data.db"), "sqlite:///var/lib/smokeping/data.db");
+        assert_eq!(redacted("sqlite::memory:"), "sqlite::memory:");
+    }
 }
@@ -0,0 +1,126 @@
+//! Startup self-check of what the process is allowed to do, and the health of the
+//! probers it started.
+
+use serde::Serialize;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{OnceLock, RwLock};
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum IcmpMode {
+    /// Raw sockets, needs CAP_NET_RAW; replies include the IP header and thus the TTL
+    Raw,
+    /// Unprivileged ICMP sockets, allowed by `net.ipv4.ping_group_range`
+    Datagram,
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct IcmpSupport {
+    pub raw: bool,
+    pub datagram: bool,
+    /// Mode ICMP probes use, `None` when neither socket type can be opened
+    pub mode: Option<IcmpMode>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct IcmpCapabilities {
+    pub ipv4: IcmpSupport,
+    pub ipv6: IcmpSupport,
+}
+
+static ICMP: OnceLock<IcmpCapabilities> = OnceLock::new();
+
+/// ICMP socket types available to the process, detected once.
+pub fn icmp() -> IcmpCapabilities {
+    *ICMP.get_or_init(|| IcmpCapabilities {
+        ipv4: detect(Domain::IPV4, Protocol::ICMPV4),
+        ipv6: detect(Domain::IPV6, Protocol::ICMPV6),
+    })
+}
+
+/// Socket type ICMP probes of `ip` should open.
+pub fn icmp_socket_type(ip: IpAddr) -> Option<Type> {
+    let support = if ip.is_ipv4() { icmp().ipv4 } else { icmp().ipv6 };
+    support.mode.map(|mode| match mode {
+        IcmpMode::Raw => Type::RAW,
+        IcmpMode::Datagram => Type::DGRAM,
+    })
+}
+
+fn detect(domain: Domain, protocol: Protocol) -> IcmpSupport {
+    let raw = Socket::new(domain, Type::RAW, Some(protocol)).is_ok();
+    let datagram = Socket::new(domain, Type::DGRAM, Some(protocol)).is_ok();
+    // Raw sockets are preferred since only they expose the reply TTL
+    let mode = if raw {
+        Some(IcmpMode::Raw)
+    } else if datagram {
+        Some(IcmpMode::Datagram)
+    } else {
+        None
+    };
+    IcmpSupport { raw, datagram, mode }
+}
+
+/// Results of the startup self-check, served by `/api/system/diagnostics`.
+#[derive(Default)]
+pub struct Diagnostics {
+    probers_started: AtomicUsize,
+    probers_stopped: AtomicUsize,
+    prober_failures: RwLock<BTreeMap<i32, String>>,
+}
+
+impl Diagnostics {
+    /// Runs the self-check and logs what probing will be able to do.
+    pub fn detect() -> Self {
+        let icmp = icmp();
+        for (family, support) in [("IPv4", icmp.ipv4), ("IPv6", icmp.ipv6)] {
+            match support.mode {
+                Some(IcmpMode::Raw) => println!("{} ICMP: using raw sockets", family),
+                Some(IcmpMode::Datagram) => println!(
+                    "{} ICMP: raw sockets not permitted (no CAP_NET_RAW), using unprivileged datagram sockets; reply TTL and hop count are not recorded",
+                    family
+                ),
+                None => eprintln!(
+                    "{} ICMP: unavailable, icmp targets will not be probed; grant CAP_NET_RAW or widen net.ipv4.ping_group_range",
+                    family
+                ),
+            }
+        }
+        Diagnostics::default()
+    }
+
+    pub fn prober_started(&self) {
+        self.probers_started.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records that the prober of `target_id` was stopped, forgetting why it failed if it did.
+    pub fn prober_stopped(&self, target_id: i32) {
+        self.probers_stopped.fetch_add(1, Ordering::Relaxed);
+        self.prober_failures.write().unwrap().remove(&target_id);
+    }
+
+    pub fn prober_failed(&self, target_id: i32, error: String) {
+        self.prober_failures.write().unwrap().insert(target_id, error);
+    }
+
+    pub fn report(&self) -> serde_json::Value {
+        let failures = self.prober_failures.read().unwrap();
+        let started = self.probers_started.load(Ordering::Relaxed);
+        let stopped = self.probers_stopped.load(Ordering::Relaxed);
+        serde_json::json!({
+            "icmp": icmp(),
+            "probers": {
+                "started": started,
+                "stopped": stopped,
+                "running": started.saturating_sub(stopped + failures.len()),
+                "failed": failures
+                    .iter()
+                    .map(|(target_id, error)| serde_json::json!({ "target_id": target_id, "error": error }))
+                    .collect::<Vec<_>>(),
+            },
+        })
+    }
+}
@@ -0,0 +1,136 @@
+//! Target labels: key validation and label selectors such as `env=prod,region!=eu`.
+
+use std::collections::BTreeMap;
+
+// Tag keys the prober already writes, which labels must not overwrite
+const RESERVED_KEYS: &[&str] = &["target_id", "is_lost", "path_id", "flow"];
+
+/// Labels are written as InfluxDB tags, so keys are kept to simple identifiers.
+pub fn validate(labels: &BTreeMap<String, String>) -> Result<(), String> {
+    for (key, value) in labels {
+        let valid = key.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+        if !valid {
+            return Err(format!("invalid label key '{}', expected a letter followed by letters, digits, '_', '-' or '.'", key));
+        }
+        if RESERVED_KEYS.contains(&key.as_str()) {
+            return Err(format!("label key '{}' is reserved", key));
+        }
+        if value.is_empty() {
+            return Err(format!("label '{}' has an empty value", key));
+        }
+    }
+    Ok(())
+}
+
+enum Requirement {
+    Equals(String, String),
+    NotEquals(String, String),
+    Exists(String),
+    NotExists(String),
+}
+
+/// Comma-separated requirements that must all hold: `key=value` (or `key==value`),
+/// `key!=value`, `key` (label present) and `!key` (label absent).
+pub struct Selector {
+    requirements: Vec<Requirement>,
+}
+
+impl Selector {
+    pub fn parse(selector: &str) -> Result<Self, String> {
+        let requirements = selector
+            .split(',')
+            .map(str::trim)
+            .filter(|requirement| !requirement.is_empty())
+            .map(|requirement| {
+                let requirement = if let Some((key, value)) = requirement.split_once("!=") {
+                    Requirement::NotEquals(key.trim().to_string(), value.trim().to_string())
+                } else if let Some((key, value)) = requirement.split_once("==").or_else(|| requirement.split_once('=')) {
+                    Requirement::Equals(key.trim().to_string(), value.trim().to_string())
+                } else if let Some(key) = requirement.strip_prefix('!') {
+                    Requirement::NotExists(key.trim().to_string())
+                } else {
+                    Requirement::Exists(requirement.to_string())
+                };
+                match &requirement {
+                    Requirement::Equals(key, _)
+                    | Requirement::NotEquals(key, _)
+                    | Requirement::Exists(key)
+                    | Requirement::NotExists(key)
+                        if key.is_empty() => Err("selector requirement without a label key".to_string()),
+                    _ => Ok(requirement),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Selector { requirements })
+    }
+
+    pub fn matches(&self, labels: &BTreeMap<String, String>) -> bool {
+        self.requirements.iter().all(|requirement| match requirement {
+            Requirement::Equals(key, value) => labels.get(key) == Some(value),
+            // Like Kubernetes, targets without the label match `!=`
+            Requirement::NotEquals(key, value) => labels.get(key) != Some(value),
+            Requirement::Exists(key) => labels.contains_key(key),
+            Requirement::NotExists(key) => !labels.contains_key(key),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn selectors_match_all_requirements() {
+        let prod_eu = labels(&[("env", "prod"), ("region", "eu")]);
+        let prod_us = labels(&[("env", "prod"), ("region", "us"), ("tier", "edge")]);
+        let bare = labels(&[]);
+
+        let selector = Selector::parse("env=prod, region!=eu").unwrap();
+        assert!(!selector.matches(&prod_eu));
+        assert!(selector.matches(&prod_us));
+        assert!(!selector.matches(&bare));
+
+        let selector = Selector::parse("env==prod,tier").unwrap();
+        assert!(!selector.matches(&prod_eu));
+        assert!(selector.matches(&prod_us));
+
+        let selector = Selector::parse("!tier").unwrap();
+        assert!(selector.matches(&prod_eu));
+        assert!(!selector.matches(&prod_us));
+        assert!(selector.matches(&bare));
+    }
+
+    #[test]
+    fn not_equals_matches_targets_without_the_label() {
+        let selector = Selector::parse("region!=eu").unwrap();
+        assert!(selector.matches(&labels(&[])));
+    }
+
+    #[test]
+    fn empty_selectors_match_everything() {
+        let selector = Selector::parse(" , ").unwrap();
+        assert!(selector.matches(&labels(&[])));
+        assert!(selector.matches(&labels(&[("env", "prod")])));
+    }
+
+    #[test]
+    fn requirements_need_a_key() {
+        assert!(Selector::parse("=prod").is_err());
+        assert!(Selector::parse("env=prod,!=eu").is_err());
+        assert!(Selector::parse("!").is_err());
+    }
+
+    #[test]
+    fn label_keys_and_values_are_validated() {
+        assert!(validate(&labels(&[("env", "prod"), ("site.rack-2_a", "x")])).is_ok());
+        assert!(validate(&labels(&[("2env", "prod")])).is_err());
+        assert!(validate(&labels(&[("env name", "prod")])).is_err());
+        assert!(validate(&labels(&[("target_id", "1")])).is_err());
+        assert!(validate(&labels(&[("env", "")])).is_err());
+    }
+}
@@ -0,0 +1,381 @@
+//! Interactive live probing over WebSocket, a browser-based `ping` for troubleshooting.
+//!
+//! A client asks for an ad-hoc probe of a host and gets every round streamed back as it
+//! completes. Nothing is stored. Hosts must match the allow-list in `LIVE_PROBE_ALLOW`
+//! and sessions are rate limited; with an empty allow-list the endpoint refuses everything.
+
+use crate::models::target::Model as Target;
+use crate::prober::{field_json, format_ip, Prober};
+use crate::AppState;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::IntoResponse;
+use futures::stream::{SplitSink, StreamExt};
+use futures::SinkExt;
+use serde::Deserialize;
+use serde_json::json;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::time::{Instant, MissedTickBehavior};
+
+pub(crate) enum AllowRule {
+    /// Exact host name, or every subdomain with a leading `*.`
+    Name(String),
+    Network(IpAddr, u8),
+}
+
+impl AllowRule {
+    pub(crate) fn parse(rule: &str) -> Option<Self> {
+        let (addr, prefix) = match rule.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (rule, None),
+        };
+        match addr.parse::<IpAddr>() {
+            Ok(ip) => {
+                let max = if ip.is_ipv4() { 32 } else { 128 };
+                let prefix = match prefix {
+                    Some(prefix) => prefix.parse::<u8>().ok().filter(|prefix| *prefix <= max)?,
+                    None => max,
+                };
+                Some(AllowRule::Network(ip, prefix))
+            }
+            Err(_) if prefix.is_none() => Some(AllowRule::Name(rule.to_ascii_lowercase())),
+            Err(_) => None,
+        }
+    }
+
+    fn matches_name(&self, host: &str) -> bool {
+        match self {
+            AllowRule::Name(name) => match name.strip_prefix("*.") {
+                Some(domain) => host.strip_suffix(domain).is_some_and(|sub| sub.ends_with('.')),
+                None => host == name,
+            },
+            AllowRule::Network(..) => false,
+        }
+    }
+
+    pub(crate) fn matches_ip(&self, ip: IpAddr) -> bool {
+        match (self, ip) {
+            (AllowRule::Network(IpAddr::V4(network), prefix), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - *prefix as u32).unwrap_or(0);
+                u32::from(*network) & mask == u32::from(ip) & mask
+            }
+            (AllowRule::Network(IpAddr::V6(network), prefix), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - *prefix as u32).unwrap_or(0);
+                u128::from(*network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Limits and allow-list of the live probing endpoint, read from the environment.
+pub struct LiveProbing {
+    allow: Vec<AllowRule>,
+    min_interval: Duration,
+    max_count: u32,
+    sessions: Arc<Semaphore>,
+}
+
+impl LiveProbing {
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok();
+
+        let mut allow = Vec::new();
+        for rule in var("LIVE_PROBE_ALLOW").unwrap_or_default().split(',').map(str::trim).filter(|rule| !rule.is_empty()) {
+            match AllowRule::parse(rule) {
+                Some(rule) => allow.push(rule),
+                None => eprintln!("Ignoring invalid LIVE_PROBE_ALLOW entry '{}'", rule),
+            }
+        }
+
+        LiveProbing {
+            allow,
+            min_interval: Duration::from_millis(var("LIVE_PROBE_MIN_INTERVAL_MS").and_then(|v| v.parse().ok()).unwrap_or(1000)),
+            max_count: var("LIVE_PROBE_MAX_COUNT").and_then(|v| v.parse().ok()).unwrap_or(100).max(1),
+            sessions: Arc::new(Semaphore::new(var("LIVE_PROBE_MAX_SESSIONS").and_then(|v| v.parse().ok()).unwrap_or(4))),
+        }
+    }
+
+    fn is_allowed(&self, host: &str, ip: IpAddr) -> bool {
+        let host = host.to_ascii_lowercase();
+        self.allow.iter().any(|rule| rule.matches_name(&host) || rule.matches_ip(ip))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "action", rename_all = "lowercase")]
+enum LiveCommand {
+    Start(LiveRequest),
+    Stop,
+}
+
+#[derive(Deserialize, Debug)]
+struct LiveRequest {
+    /// "icmp", "tcp" or "http"
+    probe: String,
+    host: String,
+    port: Option<u16>,
+    /// Request path of `http` probes
+    path: Option<String>,
+    count: Option<u32>,
+    interval_ms: Option<u64>,
+}
+
+struct Session {
+    prober: Prober,
+    interval: tokio::time::Interval,
+    seq: u32,
+    count: u32,
+    rtts: Vec<f64>,
+}
+
+type Sender = SplitSink<WebSocket, Message>;
+
+// Handler to upgrade a connection into a live probing session
+pub async fn ws_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    ws.on_upgrade(|socket| handle_socket(socket, state.live))
+}
+
+async fn handle_socket(socket: WebSocket, live: Arc<LiveProbing>) {
+    let (mut sender, mut receiver) = socket.split();
+
+    let Ok(_permit) = live.sessions.clone().try_acquire_owned() else {
+        let _ = send_error(&mut sender, "Too many live sessions, try again later").await;
+        return;
+    };
+
+    let mut session: Option<Session> = None;
+    // The minimum interval holds across sessions, so restarting cannot probe faster
+    let mut last_round: Option<Instant> = None;
+    loop {
+        tokio::select! {
+            msg = receiver.next() => {
+                let text = match msg {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+                if let Some(done) = session.take() {
+                    if send_summary(&mut sender, &done).await.is_err() {
+                        break;
+                    }
+                }
+                let sent = match serde_json::from_str::<LiveCommand>(&text) {
+                    Ok(LiveCommand::Start(request)) => match start(&live, request, last_round).await {
+                        Ok(started) => {
+                            session = Some(started);
+                            Ok(())
+                        }
+                        Err(e) => send_error(&mut sender, &e).await,
+                    },
+                    Ok(LiveCommand::Stop) => Ok(()),
+                    Err(e) => send_error(&mut sender, &format!("Invalid command: {}", e)).await,
+                };
+                if sent.is_err() {
+                    break;
+                }
+            }
+            Some(()) = next_round(&mut session) => {
+                let Some(running) = session.as_mut() else { continue };
+                running.seq += 1;
+                last_round = Some(Instant::now());
+                let sent_at = chrono::Utc::now();
+                let outcome = running.prober.probe(sent_at).await;
+                if !outcome.is_lost {
+                    running.rtts.push(outcome.rtt_ms);
+                }
+
+                let mut msg = json!({
+                    "type": "result",
+                    "seq": running.seq,
+                    "time": sent_at.to_rfc3339_opts(chrono::SecondsFormat::Nanos, true),
+                    "is_lost": outcome.is_lost,
+                    "rtt_ms": outcome.rtt_ms,
+                });
+                for (name, value) in &outcome.fields {
+                    msg[name.as_str()] = field_json(value);
+                }
+                if sender.send(Message::Text(msg.to_string())).await.is_err() {
+                    break;
+                }
+
+                if running.seq >= running.count {
+                    let done = session.take().unwrap();
+                    if send_summary(&mut sender, &done).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Resolves when the running session is due for its next round, never without one
+async fn next_round(session: &mut Option<Session>) -> Option<()> {
+    match session {
+        Some(session) => {
+            session.interval.tick().await;
+            Some(())
+        }
+        None => std::future::pending().await,
+    }
+}
+
+async fn start(live: &LiveProbing, request: LiveRequest, last_round: Option<Instant>) -> Result<Session, String> {
+    let port = match request.probe.as_str() {
+        "icmp" => 0,
+        "tcp" => request.port.ok_or("tcp probes need a port")?,
+        "http" => request.port.unwrap_or(80),
+        other => return Err(format!("Unsupported live probe '{}', expected icmp, tcp or http", other)),
+    };
+
+    // Probe the address that was checked so a second lookup cannot point elsewhere
+    let ip = tokio::net::lookup_host((request.host.as_str(), port))
+        .await
+        .map_err(|e| format!("Unable to resolve '{}': {}", request.host, e))?
+        .next()
+        .ok_or_else(|| format!("No address found for '{}'", request.host))?
+        .ip();
+    if !live.is_allowed(&request.host, ip) {
+        return Err(format!("Host '{}' ({}) is not allowed for live probing", request.host, ip));
+    }
+
+    let (probe_type, host, params) = match request.probe.as_str() {
+        "icmp" => ("icmp", ip.to_string(), json!({ "pings": 1 })),
+        "tcp" => ("tcp-banner", format!("{}:{}", format_ip(ip), port), json!({ "connect_only": true })),
+        _ => {
+            let path = request.path.as_deref().unwrap_or("/");
+            // The path is sent verbatim, so it must not be able to end the request line
+            if !path.starts_with('/') || path.chars().any(|c| c.is_control() || c.is_whitespace()) {
+                return Err(format!("Invalid path '{}', expected an absolute path without spaces or control characters", path.escape_debug()));
+            }
+            let send = format!("HEAD {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", path, request.host);
+            (
+                "tcp-banner",
+                format!("{}:{}", format_ip(ip), port),
+                json!({ "send": send, "expect": "^HTTP/1\\.[01] \\d{3}[^\\r\\n]*" }),
+            )
+        }
+    };
+
+    let target = Target {
+        id: 0,
+        name: format!("live {}", request.host),
+        host,
+        probe_type: Some(probe_type.to_string()),
+        probe_interval_secs: None,
+        probe_params: Some(params),
+        schedule: None,
+        is_active: false,
+        config_key: None,
+        group_id: None,
+        profile_id: None,
+        created_at: chrono::Utc::now(),
+        deleted_at: None,
+    };
+    let prober = Prober::new(&target).await?;
+
+    let period = Duration::from_millis(request.interval_ms.unwrap_or(1000)).max(live.min_interval);
+    let now = Instant::now();
+    let first = last_round.map_or(now, |last| (last + live.min_interval).max(now));
+    let mut interval = tokio::time::interval_at(first, period);
+    // Rounds slower than the period are not caught up in a burst
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    Ok(Session {
+        prober,
+        interval,
+        seq: 0,
+        count: request.count.unwrap_or(10).clamp(1, live.max_count),
+        rtts: Vec::new(),
+    })
+}
+
+async fn send_error(sender: &mut Sender, message: &str) -> Result<(), axum::Error> {
+    let msg = json!({ "type": "error", "message": message });
+    sender.send(Message::Text(msg.to_string())).await
+}
+
+async fn send_summary(sender: &mut Sender, session: &Session) -> Result<(), axum::Error> {
+    let received = session.rtts.len();
+    let (min, max) = session.rtts.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), rtt| (min.min(*rtt), max.max(*rtt)));
+    let msg = json!({
+        "type": "summary",
+        "sent": session.seq,
+        "received": received,
+        "lost": session.seq as usize - received,
+        "min_rtt_ms": (received > 0).then_some(min),
+        "avg_rtt_ms": (received > 0).then(|| session.rtts.iter().sum::<f64>() / received as f64),
+        "max_rtt_ms": (received > 0).then_some(max),
+    });
+    sender.send(Message::Text(msg.to_string())).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allows(rule: &str, host: &str, ip: &str) -> bool {
+        let rule = AllowRule::parse(rule).unwrap();
+        rule.matches_name(host) || rule.matches_ip(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn ipv4_networks_match_by_prefix() {
+        assert!(allows("10.0.0.0/8", "10.1.2.3", "10.1.2.3"));
+        assert!(!allows("10.0.0.0/8", "11.0.0.1", "11.0.0.1"));
+        assert!(allows("192.168.1.0/24", "192.168.1.254", "192.168.1.254"));
+        assert!(!allows("192.168.1.0/24", "192.168.2.1", "192.168.2.1"));
+        assert!(allows("0.0.0.0/0", "203.0.113.9", "203.0.113.9"));
+        // A bare address is a single host
+        assert!(allows("192.0.2.1", "192.0.2.1", "192.0.2.1"));
+        assert!(!allows("192.0.2.1", "192.0.2.2", "192.0.2.2"));
+    }
+
+    #[test]
+    fn ipv6_networks_match_by_prefix() {
+        assert!(allows("2001:db8::/32", "2001:db8:1::1", "2001:db8:1::1"));
+        assert!(!allows("2001:db8::/32", "2001:db9::1", "2001:db9::1"));
+        assert!(allows("::1", "::1", "::1"));
+        assert!(allows("::/0", "2001:db8::1", "2001:db8::1"));
+        // Families never match each other
+        assert!(!allows("::/0", "192.0.2.1", "192.0.2.1"));
+        assert!(!allows("0.0.0.0/0", "::1", "::1"));
+    }
+
+    #[test]
+    fn names_match_exactly_or_by_wildcard() {
+        assert!(allows("example.com", "example.com", "192.0.2.1"));
+        assert!(!allows("example.com", "www.example.com", "192.0.2.1"));
+        assert!(allows("*.example.com", "www.example.com", "192.0.2.1"));
+        assert!(allows("*.example.com", "a.b.example.com", "192.0.2.1"));
+        assert!(!allows("*.example.com", "example.com", "192.0.2.1"));
+        assert!(!allows("*.example.com", "badexample.com", "192.0.2.1"));
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        assert!(AllowRule::parse("10.0.0.0/33").is_none());
+        assert!(AllowRule::parse("2001:db8::/129").is_none());
+        assert!(AllowRule::parse("10.0.0.0/x").is_none());
+        assert!(AllowRule::parse("example.com/24").is_none());
+    }
+
+    #[test]
+    fn allow_lists_match_names_case_insensitively() {
+        let live = LiveProbing {
+            allow: vec![AllowRule::parse("Probe.Example.com").unwrap(), AllowRule::parse("10.0.0.0/8").unwrap()],
+            min_interval: Duration::from_secs(1),
+            max_count: 1,
+            sessions: Arc::new(Semaphore::new(1)),
+        };
+        assert!(live.is_allowed("PROBE.example.COM", "192.0.2.1".parse().unwrap()));
+        assert!(live.is_allowed("anything", "10.9.8.7".parse().unwrap()));
+        assert!(!live.is_allowed("other.example.com", "192.0.2.1".parse().unwrap()));
+    }
+}
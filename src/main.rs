@@ -3,23 +3,34 @@ use axum::{
     extract::{Request, State},
     http::StatusCode,
     response::IntoResponse,
-    routing::get,
+    routing::{delete, get, post},
     Router,
 };
 use futures::stream::StreamExt;
 use futures::SinkExt;
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
 use sea_orm::DatabaseConnection;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::net::TcpListener;
 use tokio::sync::broadcast;
 use tower_http::cors::{Any, CorsLayer};
 
+mod cli;
 mod database;
+mod diagnostics;
 mod influxdb;
+mod labels;
+mod live;
+mod maintenance;
+mod migration;
 mod models;
 mod prober;
 mod routes;
+mod schedule;
+mod sketch;
+mod smokeping_import;
+mod supervisor;
+mod targets_file;
 
 #[derive(rust_embed::Embed, Clone)]
 #[folder = "frontend/dist"]
@@ -31,10 +42,20 @@ struct AppState {
     influx_client: Arc<influxdb2::Client>,
     influx_config: Arc<influxdb::InfluxConfig>,
     tx: Arc<broadcast::Sender<String>>,
+    maintenance: Arc<maintenance::Maintenance>,
+    live: Arc<live::LiveProbing>,
+    trusted_proxies: Arc<routes::history::TrustedProxies>,
+    diagnostics: Arc<diagnostics::Diagnostics>,
+    supervisor: Arc<supervisor::Supervisor>,
 }
 
 #[tokio::main]
 async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(cli::run(&args).await);
+    }
+
     println!("Starting smokeping-rs application...");
 
     let db = database::setup_database().await.unwrap_or_else(|e| {
@@ -43,6 +64,24 @@ async fn main() {
     });
     let db = Arc::new(db);
 
+    // The targets file, when configured, owns the targets it lists
+    if let Ok(path) = std::env::var("TARGETS_FILE") {
+        let file = targets_file::load(std::path::Path::new(&path)).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+        match targets_file::sync(db.as_ref(), file).await {
+            Ok(report) => println!(
+                "Synced targets file '{}': {} created, {} updated, {} deactivated",
+                path, report.created, report.updated, report.deactivated
+            ),
+            Err(e) => {
+                eprintln!("Failed to sync targets file '{}': {:?}", path, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
     let (influx_client, influx_config) = influxdb::setup_influxdb().await.unwrap_or_else(|e| {
         eprintln!("Failed to setup InfluxDB: {:?}", e);
         std::process::exit(1);
@@ -53,36 +92,42 @@ async fn main() {
     let (tx, _) = broadcast::channel(100);
     let tx = Arc::new(tx);
 
+    let maintenance = maintenance::Maintenance::load(db.as_ref()).await.unwrap_or_else(|e| {
+        eprintln!("Failed to load maintenance windows: {:?}", e);
+        eprintln!("Continuing without scheduled maintenance...");
+        maintenance::Maintenance::default()
+    });
+    let maintenance = Arc::new(maintenance);
+
+    // Find out which ICMP sockets we may open before any prober needs one
+    let diagnostics = Arc::new(diagnostics::Diagnostics::detect());
+
+    // One prober per active target, restarted whenever the target, its profile or its groups change
+    let supervisor = Arc::new(supervisor::Supervisor::new(
+        db.clone(),
+        influx_client.as_ref().clone(),
+        influx_config.bucket.clone(),
+        tx.as_ref().clone(),
+        maintenance.clone(),
+        diagnostics.clone(),
+    ));
+    if let Err(e) = supervisor.start_all().await {
+        eprintln!("Failed to start probers: {:?}", e);
+        eprintln!("Continuing without starting probers...");
+    }
+
     let state = AppState {
         db: db.clone(),
         influx_client: influx_client.clone(),
         influx_config: influx_config.clone(),
         tx: tx.clone(),
+        maintenance: maintenance.clone(),
+        live: Arc::new(live::LiveProbing::from_env()),
+        trusted_proxies: Arc::new(routes::history::TrustedProxies::from_env()),
+        diagnostics,
+        supervisor,
     };
 
-    let targets = match models::target::Entity::find()
-        .filter(models::target::Column::IsActive.eq(true))
-        .all(db.as_ref())
-        .await
-    {
-        Ok(targets) => targets,
-        Err(e) => {
-            eprintln!("Failed to load active targets: {:?}", e);
-            eprintln!("Continuing without starting probers...");
-            Vec::new()
-        }
-    };
-
-    for target in targets {
-        let prober_state = state.clone();
-        tokio::spawn(prober::run_prober(
-            target,
-            prober_state.influx_client.as_ref().clone(),
-            prober_state.influx_config.bucket.clone(),
-            prober_state.tx.as_ref().clone(),
-        ));
-    }
-
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
@@ -90,12 +135,30 @@ async fn main() {
 
     let api_router = Router::new()
         .route("/targets", get(routes::targets::list_targets).post(routes::targets::create_target))
+        .route("/targets/bulk", post(routes::bulk::bulk_targets))
         .route("/targets/:id", get(routes::targets::get_target).put(routes::targets::update_target).delete(routes::targets::delete_target))
-        .route("/targets/:id/data", get(routes::targets::get_probe_data));
+        .route("/targets/:id/restore", post(routes::history::restore_target))
+        .route("/targets/:id/revisions", get(routes::history::list_revisions))
+        .route("/targets/:id/revisions/:revision_id/restore", post(routes::history::restore_revision))
+        .route("/targets/:id/probe", post(routes::targets::probe_target))
+        .route("/targets/:id/data", get(routes::targets::get_probe_data))
+        .route("/targets/:id/paths", get(routes::targets::get_path_data))
+        .route("/targets/:id/percentiles", get(routes::targets::get_percentiles))
+        .route("/groups", get(routes::groups::list_groups).post(routes::groups::create_group))
+        .route("/groups/:id", get(routes::groups::get_group).put(routes::groups::update_group).delete(routes::groups::delete_group))
+        .route("/profiles", get(routes::profiles::list_profiles).post(routes::profiles::create_profile))
+        .route("/profiles/:id", get(routes::profiles::get_profile).put(routes::profiles::update_profile).delete(routes::profiles::delete_profile))
+        .route("/maintenance", get(routes::maintenance::list_windows).post(routes::maintenance::create_window))
+        .route("/maintenance/:id", delete(routes::maintenance::end_window))
+        .route("/import/smokeping", post(routes::import::import_smokeping))
+        .route("/import/targets", post(routes::bulk::import_targets))
+        .route("/export/targets", get(routes::bulk::export_targets))
+        .route("/system/diagnostics", get(routes::system::get_diagnostics));
 
     let app = Router::new()
         .nest("/api", api_router)
         .route("/ws", get(ws_handler))
+        .route("/ws/live", get(live::ws_handler))
         .fallback(spa_fallback)
         .layer(cors)
         .with_state(state);
@@ -104,7 +167,7 @@ async fn main() {
     let bind_addr = format!("0.0.0.0:{}", port);
     let listener = TcpListener::bind(&bind_addr).await.unwrap();
     println!("listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
 }
 
 async fn ws_handler(
@@ -119,7 +182,13 @@ async fn handle_socket(socket: WebSocket, tx: Arc<broadcast::Sender<String>>) {
     let mut rx = tx.subscribe();
 
     tokio::spawn(async move {
-        while let Ok(msg) = rx.recv().await {
+        loop {
+            let msg = match rx.recv().await {
+                Ok(msg) => msg,
+                // A slow client missed some messages under load; keep streaming the rest
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
             if sender.send(Message::Text(msg)).await.is_err() {
                 break;
             }
@@ -133,7 +202,7 @@ async fn spa_fallback(request: Request) -> impl IntoResponse {
 
     // If the request is for an API route, return 404
     if path.starts_with("/api/") {
-        return (StatusCode::NOT_FOUND, "Not Found").into_response();
+        return routes::error::ApiError::not_found(format!("No API endpoint at {}", path)).into_response();
     }
 
     // Remove leading slash for embedded asset lookup
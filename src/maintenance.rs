@@ -0,0 +1,43 @@
+use crate::models::maintenance_window;
+use chrono::{DateTime, Utc};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use std::sync::RwLock;
+
+/// In-memory copy of the maintenance windows that have not ended yet, checked by
+/// every prober before each round.
+#[derive(Default)]
+pub struct Maintenance {
+    windows: RwLock<Vec<maintenance_window::Model>>,
+}
+
+impl Maintenance {
+    pub async fn load(db: &DatabaseConnection) -> Result<Self, sea_orm::DbErr> {
+        let windows = maintenance_window::Entity::find()
+            .filter(maintenance_window::Column::EndsAt.gt(Utc::now()))
+            .all(db)
+            .await?;
+        Ok(Maintenance { windows: RwLock::new(windows) })
+    }
+
+    pub fn is_paused(&self, target_id: i32, at: DateTime<Utc>) -> bool {
+        self.windows
+            .read()
+            .unwrap()
+            .iter()
+            .any(|window| window.is_active(at) && window.covers(target_id))
+    }
+
+    /// Adds or replaces a window, dropping the ones that are over.
+    pub fn upsert(&self, window: maintenance_window::Model) {
+        let mut windows = self.windows.write().unwrap();
+        let now = Utc::now();
+        windows.retain(|w| w.id != window.id && w.ends_at > now);
+        if window.ends_at > now {
+            windows.push(window);
+        }
+    }
+
+    pub fn remove(&self, id: i32) {
+        self.windows.write().unwrap().retain(|w| w.id != id);
+    }
+}
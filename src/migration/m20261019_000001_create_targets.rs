@@ -0,0 +1,47 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // The `targets` table exactly as databases created before migrations existed have it;
+    // `if_not_exists` adopts those, the following migrations bring them up to date
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Targets::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Targets::Id).integer().not_null().auto_increment().primary_key())
+                    .col(ColumnDef::new(Targets::Name).string().not_null())
+                    .col(ColumnDef::new(Targets::Host).string().not_null())
+                    .col(ColumnDef::new(Targets::ProbeType).string().not_null())
+                    .col(ColumnDef::new(Targets::ProbeIntervalSecs).integer().not_null())
+                    .col(ColumnDef::new(Targets::IsActive).boolean().not_null())
+                    .col(ColumnDef::new(Targets::CreatedAt).timestamp_with_time_zone().not_null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(Targets::Table).to_owned()).await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum Targets {
+    Table,
+    Id,
+    Name,
+    Host,
+    ProbeType,
+    ProbeIntervalSecs,
+    ProbeParams,
+    Schedule,
+    IsActive,
+    ConfigKey,
+    GroupId,
+    CreatedAt,
+}
@@ -0,0 +1,29 @@
+use super::m20261019_000001_create_targets::Targets;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // Databases created from the entities after this column was introduced already have it
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.has_column("targets", "probe_params").await? {
+            return Ok(());
+        }
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Targets::Table)
+                    .add_column(ColumnDef::new(Targets::ProbeParams).json().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(Table::alter().table(Targets::Table).drop_column(Targets::ProbeParams).to_owned())
+            .await
+    }
+}
@@ -0,0 +1,39 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MaintenanceWindows::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(MaintenanceWindows::Id).integer().not_null().auto_increment().primary_key())
+                    .col(ColumnDef::new(MaintenanceWindows::StartsAt).timestamp_with_time_zone().not_null())
+                    .col(ColumnDef::new(MaintenanceWindows::EndsAt).timestamp_with_time_zone().not_null())
+                    .col(ColumnDef::new(MaintenanceWindows::TargetIds).json().null())
+                    .col(ColumnDef::new(MaintenanceWindows::Reason).string().null())
+                    .col(ColumnDef::new(MaintenanceWindows::CreatedAt).timestamp_with_time_zone().not_null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(MaintenanceWindows::Table).to_owned()).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum MaintenanceWindows {
+    Table,
+    Id,
+    StartsAt,
+    EndsAt,
+    TargetIds,
+    Reason,
+    CreatedAt,
+}
@@ -0,0 +1,46 @@
+use super::m20261019_000001_create_targets::Targets;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // Databases created from the entities after this column was introduced already have it,
+    // with an inline unique constraint instead of the index
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.has_column("targets", "config_key").await? {
+            return Ok(());
+        }
+        // SQLite cannot add a unique column, so uniqueness comes from a separate index
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Targets::Table)
+                    .add_column(ColumnDef::new(Targets::ConfigKey).string().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager.create_index(config_key_index()).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop().name(CONFIG_KEY_INDEX).table(Targets::Table).to_owned())
+            .await?;
+        manager
+            .alter_table(Table::alter().table(Targets::Table).drop_column(Targets::ConfigKey).to_owned())
+            .await
+    }
+}
+
+pub const CONFIG_KEY_INDEX: &str = "idx-targets-config_key";
+
+pub fn config_key_index() -> IndexCreateStatement {
+    Index::create()
+        .name(CONFIG_KEY_INDEX)
+        .table(Targets::Table)
+        .col(Targets::ConfigKey)
+        .unique()
+        .to_owned()
+}
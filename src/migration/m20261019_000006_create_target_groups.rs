@@ -0,0 +1,48 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TargetGroups::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(TargetGroups::Id).integer().not_null().auto_increment().primary_key())
+                    .col(ColumnDef::new(TargetGroups::Name).string().not_null())
+                    .col(ColumnDef::new(TargetGroups::ParentId).integer().null())
+                    .col(ColumnDef::new(TargetGroups::DefaultProbeType).string().null())
+                    .col(ColumnDef::new(TargetGroups::DefaultProbeIntervalSecs).integer().null())
+                    .col(ColumnDef::new(TargetGroups::DefaultProbeParams).json().null())
+                    .col(ColumnDef::new(TargetGroups::CreatedAt).timestamp_with_time_zone().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-target_groups-parent_id")
+                            .from(TargetGroups::Table, TargetGroups::ParentId)
+                            .to(TargetGroups::Table, TargetGroups::Id)
+                            .on_delete(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(TargetGroups::Table).to_owned()).await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum TargetGroups {
+    Table,
+    Id,
+    Name,
+    ParentId,
+    DefaultProbeType,
+    DefaultProbeIntervalSecs,
+    DefaultProbeParams,
+    CreatedAt,
+}
@@ -0,0 +1,184 @@
+use super::m20261019_000001_create_targets::Targets;
+use super::m20261019_000005_add_target_config_key::config_key_index;
+use super::m20261019_000006_create_target_groups::TargetGroups;
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, DbBackend, Statement};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// Applied to rows that leave the probe type or interval unset when reverting
+const DEFAULT_PROBE_TYPE: &str = "icmp";
+const DEFAULT_PROBE_INTERVAL_SECS: i32 = 60;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    // Adds the group a target belongs to and lets targets leave their probe type and interval
+    // unset, to be inherited from the group
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            // SQLite cannot relax NOT NULL in place; databases created from the entities after
+            // the change already have the new table
+            if settings_required(manager).await? {
+                rebuild_sqlite(manager, true).await?;
+            }
+            return Ok(());
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Targets::Table)
+                    .modify_column(ColumnDef::new(Targets::ProbeType).string().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Targets::Table)
+                    .modify_column(ColumnDef::new(Targets::ProbeIntervalSecs).integer().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Targets::Table)
+                    .add_column(ColumnDef::new(Targets::GroupId).integer().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk-targets-group_id")
+                    .from(Targets::Table, Targets::GroupId)
+                    .to(TargetGroups::Table, TargetGroups::Id)
+                    .on_delete(ForeignKeyAction::SetNull)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() == DbBackend::Sqlite {
+            return rebuild_sqlite(manager, false).await;
+        }
+
+        manager
+            .drop_foreign_key(ForeignKey::drop().name("fk-targets-group_id").table(Targets::Table).to_owned())
+            .await?;
+        manager
+            .alter_table(Table::alter().table(Targets::Table).drop_column(Targets::GroupId).to_owned())
+            .await?;
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(Targets::Table)
+                    .value(Targets::ProbeType, DEFAULT_PROBE_TYPE)
+                    .and_where(Expr::col(Targets::ProbeType).is_null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(Targets::Table)
+                    .value(Targets::ProbeIntervalSecs, DEFAULT_PROBE_INTERVAL_SECS)
+                    .and_where(Expr::col(Targets::ProbeIntervalSecs).is_null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Targets::Table)
+                    .modify_column(ColumnDef::new(Targets::ProbeType).string().not_null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Targets::Table)
+                    .modify_column(ColumnDef::new(Targets::ProbeIntervalSecs).integer().not_null())
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+async fn settings_required(manager: &SchemaManager<'_>) -> Result<bool, DbErr> {
+    let row = manager
+        .get_connection()
+        .query_one(Statement::from_string(
+            DbBackend::Sqlite,
+            r#"SELECT "notnull" FROM pragma_table_info('targets') WHERE name = 'probe_type'"#,
+        ))
+        .await?
+        .ok_or_else(|| DbErr::Custom("targets.probe_type is missing".to_owned()))?;
+    Ok(row.try_get::<i32>("", "notnull")? != 0)
+}
+
+/// Recreates `targets` with (`grouped`) or without the group column and optional settings,
+/// the way SQLite documents for schema changes ALTER TABLE cannot make. Runs before any
+/// table references `targets`, so dropping the old table cascades nowhere.
+async fn rebuild_sqlite(manager: &SchemaManager<'_>, grouped: bool) -> Result<(), DbErr> {
+    let new_table = Alias::new("targets_new");
+    let mut create = Table::create();
+    create
+        .table(new_table.clone())
+        .col(ColumnDef::new(Targets::Id).integer().not_null().auto_increment().primary_key())
+        .col(ColumnDef::new(Targets::Name).string().not_null())
+        .col(ColumnDef::new(Targets::Host).string().not_null());
+    if grouped {
+        create
+            .col(ColumnDef::new(Targets::ProbeType).string().null())
+            .col(ColumnDef::new(Targets::ProbeIntervalSecs).integer().null());
+    } else {
+        create
+            .col(ColumnDef::new(Targets::ProbeType).string().not_null())
+            .col(ColumnDef::new(Targets::ProbeIntervalSecs).integer().not_null());
+    }
+    create
+        .col(ColumnDef::new(Targets::ProbeParams).json().null())
+        .col(ColumnDef::new(Targets::Schedule).json().null())
+        .col(ColumnDef::new(Targets::IsActive).boolean().not_null())
+        .col(ColumnDef::new(Targets::ConfigKey).string().null())
+        .col(ColumnDef::new(Targets::CreatedAt).timestamp_with_time_zone().not_null());
+    if grouped {
+        create.col(ColumnDef::new(Targets::GroupId).integer().null()).foreign_key(
+            ForeignKey::create()
+                .name("fk-targets-group_id")
+                .from(new_table.clone(), Targets::GroupId)
+                .to(TargetGroups::Table, TargetGroups::Id)
+                .on_delete(ForeignKeyAction::SetNull),
+        );
+    }
+    manager.create_table(create).await?;
+
+    let (probe_type, probe_interval_secs) = if grouped {
+        ("probe_type".to_owned(), "probe_interval_secs".to_owned())
+    } else {
+        (
+            format!("COALESCE(probe_type, '{DEFAULT_PROBE_TYPE}')"),
+            format!("COALESCE(probe_interval_secs, {DEFAULT_PROBE_INTERVAL_SECS})"),
+        )
+    };
+    let db = manager.get_connection();
+    db.execute_unprepared(&format!(
+        "INSERT INTO targets_new (id, name, host, probe_type, probe_interval_secs, probe_params, schedule, \
+         is_active, config_key, created_at) \
+         SELECT id, name, host, {probe_type}, {probe_interval_secs}, probe_params, schedule, \
+         is_active, config_key, created_at FROM targets"
+    ))
+    .await?;
+    // Keep the AUTOINCREMENT counter so ids of deleted targets are never reused
+    db.execute_unprepared("DELETE FROM sqlite_sequence WHERE name = 'targets_new'").await?;
+    db.execute_unprepared("UPDATE sqlite_sequence SET name = 'targets_new' WHERE name = 'targets'")
+        .await?;
+    db.execute_unprepared("DROP TABLE targets").await?;
+    db.execute_unprepared("ALTER TABLE targets_new RENAME TO targets").await?;
+    manager.create_index(config_key_index()).await
+}
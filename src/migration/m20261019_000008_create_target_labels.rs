@@ -0,0 +1,43 @@
+use super::m20261019_000001_create_targets::Targets;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TargetLabels::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(TargetLabels::Id).integer().not_null().auto_increment().primary_key())
+                    .col(ColumnDef::new(TargetLabels::TargetId).integer().not_null())
+                    .col(ColumnDef::new(TargetLabels::Key).string().not_null())
+                    .col(ColumnDef::new(TargetLabels::Value).string().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-target_labels-target_id")
+                            .from(TargetLabels::Table, TargetLabels::TargetId)
+                            .to(Targets::Table, Targets::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(TargetLabels::Table).to_owned()).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum TargetLabels {
+    Table,
+    Id,
+    TargetId,
+    Key,
+    Value,
+}
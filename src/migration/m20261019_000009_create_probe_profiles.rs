@@ -0,0 +1,84 @@
+use super::m20261019_000001_create_targets::Targets;
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::DbBackend;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ProbeProfiles::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(ProbeProfiles::Id).integer().not_null().auto_increment().primary_key())
+                    .col(ColumnDef::new(ProbeProfiles::Name).string().not_null().unique_key())
+                    .col(ColumnDef::new(ProbeProfiles::ProbeType).string().null())
+                    .col(ColumnDef::new(ProbeProfiles::ProbeIntervalSecs).integer().null())
+                    .col(ColumnDef::new(ProbeProfiles::Pings).integer().null())
+                    .col(ColumnDef::new(ProbeProfiles::TimeoutMs).integer().null())
+                    .col(ColumnDef::new(ProbeProfiles::ProbeParams).json().null())
+                    .col(ColumnDef::new(ProbeProfiles::AlertRttMs).double().null())
+                    .col(ColumnDef::new(ProbeProfiles::AlertLossPct).double().null())
+                    .col(ColumnDef::new(ProbeProfiles::CreatedAt).timestamp_with_time_zone().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Targets::Table)
+                    .add_column(ColumnDef::new(ProbeProfiles::ProfileId).integer().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // SQLite cannot add constraints to an existing table; there the API refuses to
+        // delete profiles that are still in use
+        if manager.get_database_backend() != DbBackend::Sqlite {
+            manager
+                .create_foreign_key(
+                    ForeignKey::create()
+                        .name("fk-targets-profile_id")
+                        .from(Targets::Table, ProbeProfiles::ProfileId)
+                        .to(ProbeProfiles::Table, ProbeProfiles::Id)
+                        .on_delete(ForeignKeyAction::Restrict)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if manager.get_database_backend() != DbBackend::Sqlite {
+            manager
+                .drop_foreign_key(ForeignKey::drop().name("fk-targets-profile_id").table(Targets::Table).to_owned())
+                .await?;
+        }
+        manager
+            .alter_table(Table::alter().table(Targets::Table).drop_column(ProbeProfiles::ProfileId).to_owned())
+            .await?;
+        manager.drop_table(Table::drop().table(ProbeProfiles::Table).to_owned()).await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ProbeProfiles {
+    Table,
+    Id,
+    Name,
+    ProbeType,
+    ProbeIntervalSecs,
+    Pings,
+    TimeoutMs,
+    ProbeParams,
+    AlertRttMs,
+    AlertLossPct,
+    CreatedAt,
+    // Column of `targets` referencing a profile
+    ProfileId,
+}
@@ -0,0 +1,61 @@
+use super::m20261019_000001_create_targets::Targets;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Targets::Table)
+                    .add_column(ColumnDef::new(TargetRevisions::DeletedAt).timestamp_with_time_zone().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(TargetRevisions::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(TargetRevisions::Id).integer().not_null().auto_increment().primary_key())
+                    .col(ColumnDef::new(TargetRevisions::TargetId).integer().not_null())
+                    .col(ColumnDef::new(TargetRevisions::Action).string().not_null())
+                    .col(ColumnDef::new(TargetRevisions::Actor).string().not_null())
+                    .col(ColumnDef::new(TargetRevisions::Snapshot).json().not_null())
+                    .col(ColumnDef::new(TargetRevisions::CreatedAt).timestamp_with_time_zone().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-target_revisions-target_id")
+                            .from(TargetRevisions::Table, TargetRevisions::TargetId)
+                            .to(Targets::Table, Targets::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(TargetRevisions::Table).to_owned()).await?;
+        manager
+            .alter_table(Table::alter().table(Targets::Table).drop_column(TargetRevisions::DeletedAt).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum TargetRevisions {
+    Table,
+    Id,
+    TargetId,
+    Action,
+    Actor,
+    Snapshot,
+    CreatedAt,
+    // Column of `targets` marking soft-deleted targets
+    DeletedAt,
+}
@@ -0,0 +1,37 @@
+//! Versioned schema migrations, applied in order and recorded in `seaql_migrations`.
+//!
+//! Migrations are never edited once released; schema changes get a new
+//! `mYYYYMMDD_NNNNNN_*` module appended to `Migrator::migrations`.
+
+pub use sea_orm_migration::prelude::*;
+
+mod m20261019_000001_create_targets;
+mod m20261019_000002_add_target_probe_params;
+mod m20261019_000003_create_maintenance_windows;
+mod m20261019_000004_add_target_schedule;
+mod m20261019_000005_add_target_config_key;
+mod m20261019_000006_create_target_groups;
+mod m20261019_000007_add_target_group_id;
+mod m20261019_000008_create_target_labels;
+mod m20261019_000009_create_probe_profiles;
+mod m20261019_000010_create_target_revisions;
+
+pub struct Migrator;
+
+#[async_trait::async_trait]
+impl MigratorTrait for Migrator {
+    fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+        vec![
+            Box::new(m20261019_000001_create_targets::Migration),
+            Box::new(m20261019_000002_add_target_probe_params::Migration),
+            Box::new(m20261019_000003_create_maintenance_windows::Migration),
+            Box::new(m20261019_000004_add_target_schedule::Migration),
+            Box::new(m20261019_000005_add_target_config_key::Migration),
+            Box::new(m20261019_000006_create_target_groups::Migration),
+            Box::new(m20261019_000007_add_target_group_id::Migration),
+            Box::new(m20261019_000008_create_target_labels::Migration),
+            Box::new(m20261019_000009_create_probe_profiles::Migration),
+            Box::new(m20261019_000010_create_target_revisions::Migration),
+        ]
+    }
+}
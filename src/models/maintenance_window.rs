@@ -0,0 +1,36 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "maintenance_windows")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub starts_at: chrono::DateTime<chrono::Utc>,
+    pub ends_at: chrono::DateTime<chrono::Utc>,
+    /// Target ids paused by this window, `None` pauses every target
+    #[sea_orm(column_type = "Json", nullable)]
+    pub target_ids: Option<Json>,
+    pub reason: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    pub fn covers(&self, target_id: i32) -> bool {
+        match &self.target_ids {
+            Some(ids) => ids
+                .as_array()
+                .is_some_and(|ids| ids.iter().any(|id| id.as_i64() == Some(target_id as i64))),
+            None => true,
+        }
+    }
+
+    pub fn is_active(&self, at: chrono::DateTime<chrono::Utc>) -> bool {
+        self.starts_at <= at && at < self.ends_at
+    }
+}
@@ -1 +1,6 @@
+pub mod maintenance_window;
+pub mod probe_profile;
 pub mod target;
+pub mod target_group;
+pub mod target_label;
+pub mod target_revision;
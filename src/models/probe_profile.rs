@@ -0,0 +1,74 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Named set of probe settings shared by the targets that reference it.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "probe_profiles")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    #[sea_orm(unique)]
+    pub name: String,
+    /// Used by member targets that leave the setting unset, ahead of their group defaults
+    pub probe_type: Option<String>,
+    pub probe_interval_secs: Option<i32>,
+    /// Echo requests per round, passed to the prober as the `pings` parameter
+    pub pings: Option<i32>,
+    /// Per-probe timeout, passed to the prober as the `timeout_ms` parameter
+    pub timeout_ms: Option<i32>,
+    #[sea_orm(column_type = "Json", nullable)]
+    pub probe_params: Option<Json>,
+    /// Median RTT above which a round counts as degraded
+    pub alert_rtt_ms: Option<f64>,
+    /// Share of lost rounds, in percent, above which a target counts as degraded
+    pub alert_loss_pct: Option<f64>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::target::Entity")]
+    Targets,
+}
+
+impl Related<super::target::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Targets.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Fills the probe settings `target` leaves unset from `profile`. Probe parameters are
+/// merged key by key, with the target's own parameters taking precedence.
+pub fn apply(target: &mut super::target::Model, profile: &Model) {
+    if target.probe_type.is_none() {
+        target.probe_type = profile.probe_type.clone();
+    }
+    if target.probe_interval_secs.is_none() {
+        target.probe_interval_secs = profile.probe_interval_secs;
+    }
+
+    let mut params = match &profile.probe_params {
+        Some(Json::Object(params)) => params.clone(),
+        _ => serde_json::Map::new(),
+    };
+    if let Some(pings) = profile.pings {
+        params.insert("pings".to_string(), pings.into());
+    }
+    if let Some(timeout_ms) = profile.timeout_ms {
+        params.insert("timeout_ms".to_string(), timeout_ms.into());
+    }
+    match target.probe_params.take() {
+        Some(Json::Object(own)) => params.extend(own),
+        // Parameters that are not an object are left for the prober to reject
+        Some(own) => {
+            target.probe_params = Some(own);
+            return;
+        }
+        None => {}
+    }
+    if !params.is_empty() {
+        target.probe_params = Some(Json::Object(params));
+    }
+}
@@ -1,5 +1,6 @@
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "targets")]
@@ -8,13 +9,111 @@ pub struct Model {
     pub id: i32,
     pub name: String,
     pub host: String,
-    pub probe_type: String,
-    pub probe_interval_secs: i32,
+    /// Inherited from the target's profile, then its groups, when unset, see [`Inherited`]
+    pub probe_type: Option<String>,
+    pub probe_interval_secs: Option<i32>,
+    /// Probe-type specific settings, e.g. the latency model of a `simulated` target
+    #[sea_orm(column_type = "Json", nullable)]
+    pub probe_params: Option<Json>,
+    /// When the target may be probed (see `schedule::ScheduleSpec`), always when unset
+    #[sea_orm(column_type = "Json", nullable)]
+    pub schedule: Option<Json>,
     pub is_active: bool,
+    /// Key of the entry in the targets file that owns this target; such targets are read-only in the API
+    #[sea_orm(unique, nullable)]
+    pub config_key: Option<String>,
+    pub group_id: Option<i32>,
+    /// Probe profile providing the settings the target leaves unset
+    pub profile_id: Option<i32>,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    /// When the target was deleted; deleted targets keep their id and history until restored
+    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
+// Used when neither the target nor any of its groups sets the probe type or interval
+pub const DEFAULT_PROBE_TYPE: &str = "icmp";
+pub const DEFAULT_PROBE_INTERVAL_SECS: i32 = 60;
+
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::target_group::Entity",
+        from = "Column::GroupId",
+        to = "super::target_group::Column::Id",
+        on_delete = "SetNull"
+    )]
+    TargetGroup,
+    #[sea_orm(
+        belongs_to = "super::probe_profile::Entity",
+        from = "Column::ProfileId",
+        to = "super::probe_profile::Column::Id",
+        on_delete = "Restrict"
+    )]
+    ProbeProfile,
+    #[sea_orm(has_many = "super::target_label::Entity")]
+    Labels,
+    #[sea_orm(has_many = "super::target_revision::Entity")]
+    Revisions,
+}
+
+impl Related<super::target_group::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TargetGroup.def()
+    }
+}
+
+impl Related<super::probe_profile::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ProbeProfile.def()
+    }
+}
+
+impl Related<super::target_label::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Labels.def()
+    }
+}
+
+impl Related<super::target_revision::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Revisions.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    pub fn effective_probe_type(&self) -> &str {
+        self.probe_type.as_deref().unwrap_or(DEFAULT_PROBE_TYPE)
+    }
+
+    pub fn effective_probe_interval_secs(&self) -> i32 {
+        self.probe_interval_secs.unwrap_or(DEFAULT_PROBE_INTERVAL_SECS)
+    }
+}
 
-impl ActiveModelBehavior for ActiveModel {}
\ No newline at end of file
+/// Groups and probe profiles, loaded once to resolve the settings of many targets.
+#[derive(Clone)]
+pub struct Inherited {
+    pub groups: HashMap<i32, super::target_group::Model>,
+    pub profiles: HashMap<i32, super::probe_profile::Model>,
+}
+
+impl Inherited {
+    pub async fn load<C: ConnectionTrait>(db: &C) -> Result<Self, DbErr> {
+        let groups = super::target_group::Entity::find().all(db).await?;
+        let profiles = super::probe_profile::Entity::find().all(db).await?;
+        Ok(Inherited {
+            groups: groups.into_iter().map(|group| (group.id, group)).collect(),
+            profiles: profiles.into_iter().map(|profile| (profile.id, profile)).collect(),
+        })
+    }
+
+    /// Fills the probe settings `target` leaves unset from its profile, then its groups.
+    pub fn apply(&self, target: &mut Model) {
+        if let Some(profile) = target.profile_id.and_then(|id| self.profiles.get(&id)) {
+            super::probe_profile::apply(target, profile);
+        }
+        super::target_group::apply_defaults(target, &self.groups);
+    }
+}
@@ -0,0 +1,55 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "target_groups")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub name: String,
+    /// Enclosing group, `None` for top-level groups such as regions
+    pub parent_id: Option<i32>,
+    /// Defaults for member targets and subgroups that leave the setting unset
+    pub default_probe_type: Option<String>,
+    pub default_probe_interval_secs: Option<i32>,
+    #[sea_orm(column_type = "Json", nullable)]
+    pub default_probe_params: Option<Json>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(belongs_to = "Entity", from = "Column::ParentId", to = "Column::Id", on_delete = "Restrict")]
+    Parent,
+    #[sea_orm(has_many = "super::target::Entity")]
+    Targets,
+}
+
+impl Related<super::target::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Targets.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Fills the probe settings `target` leaves unset from its group, then the group's
+/// ancestors, nearest first.
+pub fn apply_defaults(target: &mut super::target::Model, groups: &HashMap<i32, Model>) {
+    let mut group_id = target.group_id;
+    // Bounded by the number of groups in case the parent links ever form a loop
+    for _ in 0..groups.len() {
+        let Some(group) = group_id.and_then(|id| groups.get(&id)) else { break };
+        if target.probe_type.is_none() {
+            target.probe_type = group.default_probe_type.clone();
+        }
+        if target.probe_interval_secs.is_none() {
+            target.probe_interval_secs = group.default_probe_interval_secs;
+        }
+        if target.probe_params.is_none() {
+            target.probe_params = group.default_probe_params.clone();
+        }
+        group_id = group.parent_id;
+    }
+}
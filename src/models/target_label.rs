@@ -0,0 +1,69 @@
+use sea_orm::entity::prelude::*;
+use sea_orm::{NotSet, Set};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "target_labels")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub target_id: i32,
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::target::Entity",
+        from = "Column::TargetId",
+        to = "super::target::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Target,
+}
+
+impl Related<super::target::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Target.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Labels of every target that has any, by target id.
+pub async fn all<C: ConnectionTrait>(db: &C) -> Result<HashMap<i32, BTreeMap<String, String>>, DbErr> {
+    let mut labels: HashMap<i32, BTreeMap<String, String>> = HashMap::new();
+    for label in Entity::find().all(db).await? {
+        labels.entry(label.target_id).or_default().insert(label.key, label.value);
+    }
+    Ok(labels)
+}
+
+pub async fn for_target<C: ConnectionTrait>(db: &C, target_id: i32) -> Result<BTreeMap<String, String>, DbErr> {
+    Ok(Entity::find()
+        .filter(Column::TargetId.eq(target_id))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|label| (label.key, label.value))
+        .collect())
+}
+
+/// Replaces all labels of a target.
+pub async fn replace<C: ConnectionTrait>(db: &C, target_id: i32, labels: &BTreeMap<String, String>) -> Result<(), DbErr> {
+    Entity::delete_many().filter(Column::TargetId.eq(target_id)).exec(db).await?;
+    if labels.is_empty() {
+        return Ok(());
+    }
+    Entity::insert_many(labels.iter().map(|(key, value)| ActiveModel {
+        id: NotSet,
+        target_id: Set(target_id),
+        key: Set(key.clone()),
+        value: Set(value.clone()),
+    }))
+    .exec(db)
+    .await?;
+    Ok(())
+}
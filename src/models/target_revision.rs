@@ -0,0 +1,84 @@
+use sea_orm::entity::prelude::*;
+use sea_orm::{NotSet, QueryOrder, Set};
+use serde::{Deserialize, Serialize};
+
+/// One change of a target, with the target as it was afterwards.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "target_revisions")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub target_id: i32,
+    /// `create`, `update`, `delete` or `restore`
+    pub action: String,
+    /// Who made the change, as reported by the client or the part of smokeping-rs that made it
+    pub actor: String,
+    /// The target with its labels after the change
+    #[sea_orm(column_type = "Json")]
+    pub snapshot: Json,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::target::Entity",
+        from = "Column::TargetId",
+        to = "super::target::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Target,
+}
+
+impl Related<super::target::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Target.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Action {
+    Create,
+    Update,
+    Delete,
+    Restore,
+}
+
+impl Action {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Action::Create => "create",
+            Action::Update => "update",
+            Action::Delete => "delete",
+            Action::Restore => "restore",
+        }
+    }
+}
+
+/// Records the current state of `target` and its labels as a new revision.
+pub async fn record<C: ConnectionTrait>(db: &C, action: Action, actor: &str, target: &super::target::Model) -> Result<Model, DbErr> {
+    let labels = super::target_label::for_target(db, target.id).await?;
+    let mut snapshot = serde_json::to_value(target).map_err(|e| DbErr::Json(e.to_string()))?;
+    snapshot["labels"] = serde_json::to_value(labels).map_err(|e| DbErr::Json(e.to_string()))?;
+    ActiveModel {
+        id: NotSet,
+        target_id: Set(target.id),
+        action: Set(action.as_str().to_string()),
+        actor: Set(actor.to_string()),
+        snapshot: Set(snapshot),
+        created_at: Set(chrono::Utc::now()),
+    }
+    .insert(db)
+    .await
+}
+
+/// Revisions of `target_id`, oldest first.
+pub async fn for_target<C: ConnectionTrait>(db: &C, target_id: i32) -> Result<Vec<Model>, DbErr> {
+    Entity::find()
+        .filter(Column::TargetId.eq(target_id))
+        .order_by_asc(Column::Id)
+        .all(db)
+        .await
+}
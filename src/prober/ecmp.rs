@@ -0,0 +1,292 @@
+//! Paris-traceroute style enumeration of equal-cost multipath (ECMP) routes.
+//!
+//! Every flow is a UDP traceroute with a fixed source/destination port pair, so routers
+//! hashing on the 5-tuple keep it on one path, while different flows use different source
+//! ports to land on different paths. Flow `n` always uses the same source port, so a flow can be
+//! followed from round to round; rounds sharing a port range therefore run one at a time. ICMP
+//! errors are read from the socket error queue (`IP_RECVERR`), which works without raw socket
+//! privileges.
+
+use super::ProbeOutcome;
+use crate::models::target::Model as Target;
+use influxdb2::models::DataPoint;
+use nix::libc;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::sys::socket::{recvmsg, setsockopt, sockopt, ControlMessageOwned, MsgFlags, SockaddrIn};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::IoSliceMut;
+use std::net::{Ipv4Addr, UdpSocket};
+use std::os::fd::{AsFd, AsRawFd};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const ICMP_TIME_EXCEEDED: u8 = 11;
+const ICMP_DEST_UNREACHABLE: u8 = 3;
+const ICMP_PORT_UNREACHABLE: u8 = 3;
+
+// One lock per first source port, held for a whole round so scheduled and on-demand rounds
+// of a target never try to bind the same ports
+static ROUNDS: Mutex<BTreeMap<u16, Arc<tokio::sync::Mutex<()>>>> = Mutex::new(BTreeMap::new());
+
+/// Settings for the `ecmp` probe type, read from `probe_params`.
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct EcmpParams {
+    pub flows: u16,
+    pub max_hops: u8,
+    /// Flow `n` uses source port `source_port + n`
+    pub source_port: u16,
+    pub dest_port: u16,
+    pub timeout_ms: u64,
+}
+
+impl Default for EcmpParams {
+    fn default() -> Self {
+        EcmpParams {
+            flows: 8,
+            max_hops: 30,
+            // Below Linux's ephemeral range, so the kernel does not hand these out to others
+            source_port: 31000,
+            dest_port: 33434,
+            timeout_ms: 3000,
+        }
+    }
+}
+
+pub struct EcmpProber {
+    target_id: i32,
+    dest: Ipv4Addr,
+    params: EcmpParams,
+}
+
+struct FlowTrace {
+    source_port: u16,
+    hops: Vec<Option<Ipv4Addr>>,
+    rtt_ms: Option<f64>,
+}
+
+impl FlowTrace {
+    // FNV-1a over the hops that answered, so path ids stay stable across restarts and releases
+    // and a hop that missed its reply does not make the path look new
+    fn path_id(&self) -> String {
+        let answered = self.hops.iter().flatten().map(Ipv4Addr::to_string).collect::<Vec<_>>().join(",");
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in answered.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        format!("{:016x}", hash)
+    }
+
+    fn hops_string(&self) -> String {
+        self.hops
+            .iter()
+            .map(|hop| hop.map(|ip| ip.to_string()).unwrap_or_else(|| "*".to_string()))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+impl EcmpProber {
+    pub fn new(target: &Target) -> Result<Self, String> {
+        let params: EcmpParams = match &target.probe_params {
+            Some(params) => serde_json::from_value(params.clone())
+                .map_err(|e| format!("Invalid ECMP probe parameters for target {}: {}", target.id, e))?,
+            None => EcmpParams::default(),
+        };
+        if params.flows == 0 || params.max_hops == 0 {
+            return Err(format!("ECMP probe for target {} needs at least one flow and one hop", target.id));
+        }
+        if params.source_port == 0 || flow_source_port(params.source_port, params.flows - 1).is_none() {
+            return Err(format!(
+                "ECMP probe for target {} needs source ports {} to {} between 1 and 65535",
+                target.id,
+                params.source_port,
+                params.source_port as u32 + params.flows as u32 - 1
+            ));
+        }
+
+        let dest: Ipv4Addr = target
+            .host
+            .parse()
+            .map_err(|e| format!("Invalid IPv4 address '{}' for target {}: {}", target.host, target.id, e))?;
+
+        Ok(EcmpProber { target_id: target.id, dest, params })
+    }
+
+    pub async fn probe(&mut self) -> ProbeOutcome {
+        let round = ROUNDS.lock().unwrap().entry(self.params.source_port).or_default().clone();
+        let _round = round.lock().await;
+
+        let timeout = Duration::from_millis(self.params.timeout_ms);
+        // `new` checked that every flow has a source port
+        let source_ports = (0..self.params.flows).filter_map(|flow| flow_source_port(self.params.source_port, flow));
+        let traces = source_ports.map(|source_port| {
+            let dest = self.dest;
+            let dest_port = self.params.dest_port;
+            let max_hops = self.params.max_hops;
+            tokio::task::spawn_blocking(move || trace_flow(dest, source_port, dest_port, max_hops, timeout))
+        });
+
+        let mut flows = Vec::new();
+        for result in futures::future::join_all(traces).await {
+            match result {
+                Ok(Ok(trace)) => flows.push(trace),
+                Ok(Err(e)) => eprintln!("ECMP trace for target {} failed: {}", self.target_id, e),
+                Err(e) => eprintln!("ECMP trace task for target {} panicked: {}", self.target_id, e),
+            }
+        }
+
+        let rtts: Vec<f64> = flows.iter().filter_map(|flow| flow.rtt_ms).collect();
+        let mut outcome = if rtts.is_empty() {
+            ProbeOutcome::lost()
+        } else {
+            ProbeOutcome::from_samples(rtts)
+        };
+
+        let mut paths: Vec<String> = Vec::new();
+        for flow in &flows {
+            let hops = flow.hops_string();
+            let path_id = flow.path_id();
+            if !paths.contains(&path_id) {
+                paths.push(path_id.clone());
+            }
+            outcome.extra_points.push(
+                DataPoint::builder("path_data")
+                    .tag("path_id", path_id)
+                    .tag("flow", flow.source_port.to_string())
+                    .tag("is_lost", flow.rtt_ms.is_none().to_string())
+                    .field("rtt_ms", flow.rtt_ms.unwrap_or(0.0))
+                    .field("hops", hops),
+            );
+        }
+
+        outcome.field("ecmp_flows", flows.len() as i64);
+        outcome.field("ecmp_flows_lost", flows.iter().filter(|flow| flow.rtt_ms.is_none()).count() as i64);
+        outcome.field("ecmp_path_count", paths.len() as i64);
+        outcome
+    }
+}
+
+// Source port of flow `flow`, or `None` when it would run past the last port
+fn flow_source_port(source_port: u16, flow: u16) -> Option<u16> {
+    source_port.checked_add(flow)
+}
+
+fn trace_flow(
+    dest: Ipv4Addr,
+    source_port: u16,
+    dest_port: u16,
+    max_hops: u8,
+    timeout: Duration,
+) -> Result<FlowTrace, String> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, source_port))
+        .map_err(|e| format!("failed to bind UDP source port {}: {}", source_port, e))?;
+    setsockopt(&socket, sockopt::Ipv4RecvErr, &true).map_err(|e| format!("failed to enable IP_RECVERR: {}", e))?;
+
+    // The payload carries the TTL so each ICMP error can be matched to its probe
+    let mut sent_at = vec![None; max_hops as usize];
+    for ttl in 1..=max_hops {
+        socket.set_ttl(ttl as u32).map_err(|e| e.to_string())?;
+        if socket.send_to(&[ttl], (dest, dest_port)).is_ok() {
+            sent_at[ttl as usize - 1] = Some(Instant::now());
+        }
+    }
+
+    let mut hops: Vec<Option<Ipv4Addr>> = vec![None; max_hops as usize];
+    let mut dest_hop: Option<(u8, f64)> = None;
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        // Done once the destination answered and every hop before it has reported in
+        if let Some((dest_ttl, _)) = dest_hop {
+            if hops[..dest_ttl as usize].iter().all(Option::is_some) {
+                break;
+            }
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        let wait = PollTimeout::from(remaining.as_millis().min(u16::MAX as u128) as u16);
+        let mut fds = [PollFd::new(socket.as_fd(), PollFlags::empty())];
+        match poll(&mut fds, wait) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(nix::errno::Errno::EINTR) => continue,
+            Err(e) => return Err(format!("poll failed: {}", e)),
+        }
+
+        while let Some((ttl, offender, reached)) = read_error(&socket) {
+            if ttl == 0 || ttl > max_hops {
+                continue;
+            }
+            let index = ttl as usize - 1;
+            hops[index] = Some(offender);
+            if reached && dest_hop.is_none_or(|(dest_ttl, _)| ttl < dest_ttl) {
+                let rtt_ms = sent_at[index].map(|sent| sent.elapsed().as_secs_f64() * 1000.0).unwrap_or(0.0);
+                dest_hop = Some((ttl, rtt_ms));
+            }
+        }
+    }
+
+    // Only keep the hops up to the destination, or up to the last one that answered
+    let path_len = match dest_hop {
+        Some((dest_ttl, _)) => dest_ttl as usize,
+        None => hops.iter().rposition(Option::is_some).map_or(0, |last| last + 1),
+    };
+    hops.truncate(path_len);
+
+    Ok(FlowTrace {
+        source_port,
+        hops,
+        rtt_ms: dest_hop.map(|(_, rtt_ms)| rtt_ms),
+    })
+}
+
+/// Pops one ICMP error from the socket error queue, returning the probe TTL, the
+/// address that sent the error and whether it came from the destination.
+fn read_error(socket: &UdpSocket) -> Option<(u8, Ipv4Addr, bool)> {
+    loop {
+        let mut payload = [0u8; 64];
+        let mut iov = [IoSliceMut::new(&mut payload)];
+        let mut cmsg = nix::cmsg_space!(libc::sock_extended_err, libc::sockaddr_in);
+        let msg = recvmsg::<SockaddrIn>(socket.as_raw_fd(), &mut iov, Some(&mut cmsg), MsgFlags::MSG_ERRQUEUE).ok()?;
+
+        let error = msg.cmsgs().ok()?.find_map(|cmsg| match cmsg {
+            ControlMessageOwned::Ipv4RecvErr(err, Some(offender)) if err.ee_origin == libc::SO_EE_ORIGIN_ICMP => {
+                Some((err, Ipv4Addr::from(u32::from_be(offender.sin_addr.s_addr))))
+            }
+            _ => None,
+        });
+        let bytes = msg.bytes;
+        let Some((err, offender)) = error else { continue };
+        if bytes == 0 {
+            continue;
+        }
+
+        let reached = err.ee_type == ICMP_DEST_UNREACHABLE && err.ee_code == ICMP_PORT_UNREACHABLE;
+        if err.ee_type == ICMP_TIME_EXCEEDED || err.ee_type == ICMP_DEST_UNREACHABLE {
+            return Some((payload[0], offender, reached));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flows_use_consecutive_source_ports() {
+        assert_eq!(flow_source_port(31000, 0), Some(31000));
+        assert_eq!(flow_source_port(31000, 7), Some(31007));
+    }
+
+    #[test]
+    fn flows_past_the_last_port_have_no_source_port() {
+        assert_eq!(flow_source_port(65530, 5), Some(65535));
+        assert_eq!(flow_source_port(65530, 6), None);
+    }
+}
@@ -0,0 +1,118 @@
+use super::ProbeOutcome;
+use crate::models::target::Model as Target;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+use tonic_health::pb::health_check_response::ServingStatus;
+use tonic_health::pb::health_client::HealthClient;
+use tonic_health::pb::HealthCheckRequest;
+
+/// Settings for the `grpc` probe type, read from `probe_params`.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct GrpcParams {
+    /// Appended to `host` when set, otherwise `host` must already be `host:port`; gRPC servers
+    /// have no well-known port to fall back to
+    pub port: Option<u16>,
+    /// Service name sent in the health check; empty checks the server as a whole
+    pub service: String,
+    pub tls: bool,
+    /// Overrides the name used for SNI and certificate verification
+    pub tls_domain: Option<String>,
+    pub timeout_ms: Option<u64>,
+}
+
+pub struct GrpcProber {
+    target_id: i32,
+    endpoint: Endpoint,
+    service: String,
+    client: Option<HealthClient<Channel>>,
+}
+
+impl GrpcProber {
+    pub fn new(target: &Target) -> Result<Self, String> {
+        let params: GrpcParams = match &target.probe_params {
+            Some(params) => serde_json::from_value(params.clone())
+                .map_err(|e| format!("Invalid gRPC probe parameters for target {}: {}", target.id, e))?,
+            None => GrpcParams::default(),
+        };
+
+        // IPv6 addresses go into the URI in brackets
+        let address = match params.port {
+            Some(port) => super::join_host_port(&target.host, port),
+            None => target.host.clone(),
+        };
+        let scheme = if params.tls { "https" } else { "http" };
+        let timeout = Duration::from_millis(params.timeout_ms.unwrap_or(2000));
+
+        let mut endpoint = Endpoint::from_shared(format!("{}://{}", scheme, address))
+            .map_err(|e| format!("Invalid gRPC address '{}' for target {}: {}", address, target.id, e))?
+            .connect_timeout(timeout)
+            .timeout(timeout);
+        if params.tls {
+            let mut tls = ClientTlsConfig::new().with_webpki_roots();
+            if let Some(domain) = params.tls_domain {
+                tls = tls.domain_name(domain);
+            }
+            endpoint = endpoint
+                .tls_config(tls)
+                .map_err(|e| format!("Invalid TLS configuration for target {}: {}", target.id, e))?;
+        }
+
+        Ok(GrpcProber {
+            target_id: target.id,
+            endpoint,
+            service: params.service,
+            client: None,
+        })
+    }
+
+    pub async fn probe(&mut self) -> ProbeOutcome {
+        // Keep the channel between rounds like a real client would, reconnecting after failures
+        let mut connect_ms = None;
+        if self.client.is_none() {
+            let started = Instant::now();
+            match self.endpoint.connect().await {
+                Ok(channel) => {
+                    connect_ms = Some(started.elapsed().as_secs_f64() * 1000.0);
+                    self.client = Some(HealthClient::new(channel));
+                }
+                Err(e) => {
+                    eprintln!("gRPC connection to target {} failed: {}", self.target_id, e);
+                    let mut outcome = ProbeOutcome::lost();
+                    outcome.field("grpc_status", "CONNECT_FAILED");
+                    return outcome;
+                }
+            }
+        }
+        let client = self.client.as_mut().unwrap();
+
+        let started = Instant::now();
+        let request = HealthCheckRequest { service: self.service.clone() };
+        let mut outcome = match client.check(request).await {
+            Ok(response) => {
+                let rtt_ms = started.elapsed().as_secs_f64() * 1000.0;
+                let status = ServingStatus::try_from(response.into_inner().status).unwrap_or(ServingStatus::Unknown);
+                let mut outcome = if status == ServingStatus::Serving {
+                    ProbeOutcome::reply(rtt_ms)
+                } else {
+                    ProbeOutcome::lost()
+                };
+                outcome.field("grpc_status", status.as_str_name());
+                outcome
+            }
+            Err(status) => {
+                // Drop the channel so the next round reconnects
+                self.client = None;
+                let mut outcome = ProbeOutcome::lost();
+                outcome.field("grpc_status", format!("{:?}", status.code()));
+                outcome
+            }
+        };
+
+        if let Some(connect_ms) = connect_ms {
+            outcome.field("grpc_connect_ms", connect_ms);
+        }
+        outcome
+    }
+}
@@ -0,0 +1,204 @@
+use super::ProbeOutcome;
+use crate::models::target::Model as Target;
+use serde::Deserialize;
+use std::net::IpAddr;
+use std::time::Duration;
+use surge_ping::{Client as PingClient, Config, IcmpPacket, PingIdentifier, PingSequence, ICMP};
+
+// Shortest wait for an echo reply a round may leave each ping; shorter ones would report
+// most replies as lost
+pub const MIN_PING_TIMEOUT_MS: u64 = 100;
+
+/// Checks that the round's pings, sent one after another, each get a reasonable share of
+/// the interval.
+pub fn validate_round(probe_params: Option<&serde_json::Value>, interval_secs: i32) -> Result<(), String> {
+    let pings = probe_params.and_then(|params| params.get("pings")).and_then(|pings| pings.as_u64()).unwrap_or(1).max(1);
+    let per_ping_ms = interval_secs.max(0) as u64 * 1000 / pings;
+    if per_ping_ms < MIN_PING_TIMEOUT_MS {
+        return Err(format!(
+            "{} pings every {} seconds leave {} ms per ping, at least {} ms are needed",
+            pings, interval_secs, per_ping_ms, MIN_PING_TIMEOUT_MS
+        ));
+    }
+    Ok(())
+}
+
+/// Settings for the `icmp` probe type, read from `probe_params`.
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct IcmpParams {
+    /// Echo requests sent per round; the round RTT is their median
+    pub pings: u16,
+    /// How long to wait for each echo reply; shortened so the round's pings fit in the interval
+    pub timeout_ms: u64,
+}
+
+impl Default for IcmpParams {
+    fn default() -> Self {
+        IcmpParams { pings: 1, timeout_ms: 2000 }
+    }
+}
+
+pub struct IcmpProber {
+    target_id: i32,
+    pings: u16,
+    timeout: Duration,
+    ping_client: PingClient,
+    host_ip: IpAddr,
+    // Hop count of the previous answered round, used to spot route changes
+    last_hop_count: Option<u8>,
+}
+
+impl IcmpProber {
+    pub async fn new(target: &Target) -> Result<Self, String> {
+        let params: IcmpParams = match &target.probe_params {
+            Some(params) => serde_json::from_value(params.clone())
+                .map_err(|e| format!("Invalid ICMP probe parameters for target {}: {}", target.id, e))?,
+            None => IcmpParams::default(),
+        };
+
+        // Host names are resolved once, when the prober starts
+        let host_ip: IpAddr = match target.host.parse() {
+            Ok(ip) => ip,
+            Err(_) => tokio::net::lookup_host((target.host.as_str(), 0))
+                .await
+                .map_err(|e| format!("Unable to resolve host '{}' for target {}: {}", target.host, target.id, e))?
+                .next()
+                .ok_or_else(|| format!("No address found for host '{}' of target {}", target.host, target.id))?
+                .ip(),
+        };
+
+        // Create ping client with the socket type found usable at startup
+        let sock_type = crate::diagnostics::icmp_socket_type(host_ip)
+            .ok_or_else(|| format!("No ICMP socket available for target {}, see /api/system/diagnostics", target.id))?;
+        let kind = if host_ip.is_ipv4() { ICMP::V4 } else { ICMP::V6 };
+        let config = Config::builder().kind(kind).sock_type_hint(sock_type).build();
+        let ping_client = PingClient::new(&config)
+            .map_err(|e| format!("Failed to create ping client for target {}: {}", target.id, e))?;
+
+        // Pings are sent one after another, so all of them waiting out the timeout must not
+        // run into the next round
+        let pings = params.pings.max(1);
+        let interval = Duration::from_secs(target.effective_probe_interval_secs() as u64);
+        let timeout = Duration::from_millis(params.timeout_ms).min(interval / pings as u32);
+
+        Ok(IcmpProber {
+            target_id: target.id,
+            pings,
+            timeout,
+            ping_client,
+            host_ip,
+            last_hop_count: None,
+        })
+    }
+
+    pub async fn probe(&mut self) -> ProbeOutcome {
+        // Create pinger and perform the round's pings
+        let mut pinger = self.ping_client.pinger(self.host_ip, PingIdentifier(0)).await;
+        pinger.timeout(self.timeout);
+        let mut samples = Vec::with_capacity(self.pings as usize);
+        let mut last_packet = None;
+        for seq in 0..self.pings {
+            if let Ok((packet, duration)) = pinger.ping(PingSequence(seq), &[0u8; 64]).await {
+                samples.push(duration.as_secs_f64() * 1000.0);
+                last_packet = Some(packet);
+            }
+        }
+        let pings_lost = (self.pings as usize - samples.len()) as i64;
+
+        let Some(packet) = last_packet else {
+            let mut outcome = ProbeOutcome::lost();
+            outcome.field("pings_lost", pings_lost);
+            return outcome;
+        };
+
+        let mut outcome = ProbeOutcome::from_samples(samples);
+        outcome.field("pings_lost", pings_lost);
+        let reply = reply_info(&packet, self.host_ip);
+        outcome.field("reply_source", reply.source.to_string());
+        outcome.field("source_mismatch", reply.source_mismatch);
+
+        // Datagram ICMP sockets do not expose the IP header, so the TTL may be unknown
+        if let Some(ttl) = reply.ttl {
+            let hop_count = inferred_hop_count(ttl);
+            let hop_count_changed = self.last_hop_count.is_some_and(|last| last != hop_count);
+            if hop_count_changed {
+                println!(
+                    "Hop count for target {} changed from {} to {} (reply TTL {})",
+                    self.target_id,
+                    self.last_hop_count.unwrap_or_default(),
+                    hop_count,
+                    ttl
+                );
+            }
+            self.last_hop_count = Some(hop_count);
+
+            outcome.field("reply_ttl", ttl as i64);
+            outcome.field("hop_count", hop_count as i64);
+            outcome.field("hop_count_changed", hop_count_changed);
+        }
+
+        outcome
+    }
+}
+
+struct ReplyInfo {
+    ttl: Option<u8>,
+    source: IpAddr,
+    source_mismatch: bool,
+}
+
+fn reply_info(packet: &IcmpPacket, target: IpAddr) -> ReplyInfo {
+    let (ttl, source) = match packet {
+        IcmpPacket::V4(packet) => (packet.get_ttl(), IpAddr::V4(packet.get_source())),
+        // ICMPv6 sockets never hand us the IPv6 header, so the hop limit is not known
+        IcmpPacket::V6(packet) => (None, IpAddr::V6(packet.get_source())),
+    };
+
+    ReplyInfo {
+        ttl,
+        source,
+        source_mismatch: source != target,
+    }
+}
+
+// Hosts start from one of a few well-known initial TTLs; assume the smallest one
+// that could have produced the observed value.
+fn inferred_hop_count(ttl: u8) -> u8 {
+    let initial = [32u8, 64, 128, 255]
+        .into_iter()
+        .find(|initial| *initial >= ttl)
+        .unwrap_or(255);
+    initial - ttl
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn hop_counts_assume_the_smallest_initial_ttl() {
+        assert_eq!(inferred_hop_count(64), 0);
+        assert_eq!(inferred_hop_count(57), 7);
+        assert_eq!(inferred_hop_count(32), 0);
+        assert_eq!(inferred_hop_count(20), 12);
+        assert_eq!(inferred_hop_count(65), 63);
+        assert_eq!(inferred_hop_count(120), 8);
+        assert_eq!(inferred_hop_count(129), 126);
+        assert_eq!(inferred_hop_count(255), 0);
+        assert_eq!(inferred_hop_count(0), 32);
+    }
+
+    #[test]
+    fn rounds_need_enough_time_per_ping() {
+        assert!(validate_round(None, 1).is_ok());
+        assert!(validate_round(Some(&json!({ "pings": 10 })), 1).is_ok());
+        assert!(validate_round(Some(&json!({ "pings": 11 })), 1).is_err());
+        assert!(validate_round(Some(&json!({ "pings": 1000 })), 100).is_ok());
+        assert_eq!(
+            validate_round(Some(&json!({ "pings": 1000 })), 1).unwrap_err(),
+            "1000 pings every 1 seconds leave 1 ms per ping, at least 100 ms are needed"
+        );
+    }
+}
@@ -1,67 +1,375 @@
+use crate::diagnostics::Diagnostics;
+use crate::maintenance::Maintenance;
 use crate::models::target::Model as Target;
 use influxdb2::Client;
-use influxdb2::models::DataPoint;
+use influxdb2::models::data_point::DataPointBuilder;
+use influxdb2::models::{DataPoint, FieldValue};
 use serde_json::json;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::sync::Arc;
 use std::time::Duration;
-use surge_ping::{Client as PingClient, Config, PingIdentifier, PingSequence};
 use tokio::sync::broadcast;
 use tokio::time;
-use std::net::IpAddr;
 
-pub async fn run_prober(target: Target, client: Client, bucket: String, tx: broadcast::Sender<String>) {
-    let mut interval = time::interval(Duration::from_secs(target.probe_interval_secs as u64));
+#[cfg(target_os = "linux")]
+mod ecmp;
+mod grpc;
+mod icmp;
+mod ntp;
+mod simulated;
+mod snmp;
+mod tcp_banner;
+mod websocket;
+
+/// Result of a single probe round, independent of the probe type.
+pub struct ProbeOutcome {
+    pub is_lost: bool,
+    pub rtt_ms: f64,
+    /// Every RTT measured in the round, summarized into the round's quantile sketch;
+    /// when empty, an answered round contributes `rtt_ms` alone
+    pub samples: Vec<f64>,
+    /// Extra per-round fields specific to the probe type
+    pub fields: Vec<(String, FieldValue)>,
+    /// Points for other measurements written with the round; `target_id` and the
+    /// round timestamp are added when the round is stored
+    pub extra_points: Vec<DataPointBuilder>,
+}
+
+impl ProbeOutcome {
+    pub fn reply(rtt_ms: f64) -> Self {
+        ProbeOutcome {
+            is_lost: false,
+            rtt_ms,
+            samples: Vec::new(),
+            fields: Vec::new(),
+            extra_points: Vec::new(),
+        }
+    }
+
+    /// Answered round made of several measurements, reported with their median RTT.
+    pub fn from_samples(mut samples: Vec<f64>) -> Self {
+        samples.sort_by(|a, b| a.total_cmp(b));
+        let mut outcome = ProbeOutcome::reply(samples[samples.len() / 2]);
+        outcome.samples = samples;
+        outcome
+    }
+
+    pub fn lost() -> Self {
+        ProbeOutcome {
+            is_lost: true,
+            rtt_ms: 0.0,
+            samples: Vec::new(),
+            fields: Vec::new(),
+            extra_points: Vec::new(),
+        }
+    }
+
+    pub fn field(&mut self, name: impl Into<String>, value: impl Into<FieldValue>) {
+        self.fields.push((name.into(), value.into()));
+    }
+}
+
+/// A configured probe of one target, kept across rounds.
+pub enum Prober {
+    Icmp(icmp::IcmpProber),
+    Simulated(Box<simulated::SimulatedProber>),
+    Snmp(snmp::SnmpProber),
+    Ntp(ntp::NtpProber),
+    Grpc(Box<grpc::GrpcProber>),
+    WebSocket(websocket::WebSocketProber),
+    TcpBanner(tcp_banner::TcpBannerProber),
+    #[cfg(target_os = "linux")]
+    Ecmp(ecmp::EcmpProber),
+}
+
+/// Probe types `Prober::new` accepts.
+pub const PROBE_TYPES: &[&str] = &[
+    "icmp",
+    "simulated",
+    "snmp",
+    "ntp",
+    "grpc",
+    "websocket",
+    "tcp-banner",
+    #[cfg(target_os = "linux")]
+    "ecmp",
+];
+
+// One day; longer intervals are better served by a schedule
+pub const MAX_PROBE_INTERVAL_SECS: i32 = 86_400;
+
+pub fn validate_probe_type(probe_type: &str) -> Result<(), String> {
+    if PROBE_TYPES.contains(&probe_type) {
+        Ok(())
+    } else {
+        Err(format!("unknown probe type '{}', expected one of {}", probe_type, PROBE_TYPES.join(", ")))
+    }
+}
+
+pub fn validate_interval(secs: i32) -> Result<(), String> {
+    if (1..=MAX_PROBE_INTERVAL_SECS).contains(&secs) {
+        Ok(())
+    } else {
+        Err(format!("must be between 1 and {} seconds", MAX_PROBE_INTERVAL_SECS))
+    }
+}
+
+/// Checks that a round with the target's effective settings fits in its interval.
+pub fn validate_round(probe_type: &str, probe_params: Option<&serde_json::Value>, interval_secs: i32) -> Result<(), String> {
+    match probe_type {
+        "icmp" => icmp::validate_round(probe_params, interval_secs),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that `host` has the form `probe_type` expects, given the target's effective
+/// `probe_params`, without resolving it.
+pub fn validate_host(probe_type: &str, host: &str, probe_params: Option<&serde_json::Value>) -> Result<(), String> {
+    let port_param = probe_params.and_then(|params| params.get("port")).is_some_and(|port| !port.is_null());
+    let (valid, expected) = match probe_type {
+        "ecmp" => (host.parse::<std::net::Ipv4Addr>().is_ok(), "an IPv4 address"),
+        // The URL may also be given in the probe parameters, leaving the host as a plain name
+        "websocket" => (
+            host.strip_prefix("ws://")
+                .or_else(|| host.strip_prefix("wss://"))
+                .is_some_and(|rest| !rest.is_empty() && !rest.contains(char::is_whitespace))
+                || is_address(host),
+            "a ws:// or wss:// URL or a host name",
+        ),
+        // These have no default port: it is appended from the probe parameters or given in the host
+        "grpc" | "tcp-banner" if port_param => (is_address(host), "an IP address or host name without a port, as `port` is set"),
+        "grpc" | "tcp-banner" => (
+            is_address_with_port(host),
+            "host:port, as `port` is not set in the probe parameters",
+        ),
+        _ => (is_address(host), "an IP address or host name"),
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("'{}' is not {}", host, expected))
+    }
+}
+
+fn is_hostname(name: &str) -> bool {
+    let name = name.strip_suffix('.').unwrap_or(name);
+    !name.is_empty()
+        && name.len() <= 253
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+/// `ip` as written in front of a port, with IPv6 addresses in brackets.
+pub fn format_ip(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{}]", ip),
+    }
+}
+
+/// `host:port` for a host name or IP address, bracketing IPv6 addresses.
+pub fn join_host_port(host: &str, port: u16) -> String {
+    match host.parse::<IpAddr>() {
+        Ok(ip) => format!("{}:{}", format_ip(ip), port),
+        Err(_) => format!("{}:{}", host, port),
+    }
+}
+
+fn is_address(host: &str) -> bool {
+    host.parse::<IpAddr>().is_ok() || is_hostname(host)
+}
+
+fn is_address_with_port(host: &str) -> bool {
+    let Some((address, port)) = host.rsplit_once(':') else { return false };
+    let address_ok = match address.strip_prefix('[').and_then(|address| address.strip_suffix(']')) {
+        Some(v6) => v6.parse::<std::net::Ipv6Addr>().is_ok(),
+        None => !address.contains(':') && is_address(address),
+    };
+    address_ok && port.parse::<u16>().is_ok_and(|port| port > 0)
+}
+
+impl Prober {
+    pub async fn new(target: &Target) -> Result<Self, String> {
+        match target.effective_probe_type() {
+            "icmp" => Ok(Prober::Icmp(icmp::IcmpProber::new(target).await?)),
+            "simulated" => Ok(Prober::Simulated(Box::new(simulated::SimulatedProber::new(target)?))),
+            "snmp" => Ok(Prober::Snmp(snmp::SnmpProber::new(target)?)),
+            "ntp" => Ok(Prober::Ntp(ntp::NtpProber::new(target)?)),
+            "grpc" => Ok(Prober::Grpc(Box::new(grpc::GrpcProber::new(target)?))),
+            "websocket" => Ok(Prober::WebSocket(websocket::WebSocketProber::new(target)?)),
+            "tcp-banner" => Ok(Prober::TcpBanner(tcp_banner::TcpBannerProber::new(target)?)),
+            #[cfg(target_os = "linux")]
+            "ecmp" => Ok(Prober::Ecmp(ecmp::EcmpProber::new(target)?)),
+            other => Err(format!("Unsupported probe type '{}' for target {}", other, target.id)),
+        }
+    }
+
+    pub async fn probe(&mut self, sent_at: chrono::DateTime<chrono::Utc>) -> ProbeOutcome {
+        match self {
+            Prober::Icmp(prober) => prober.probe().await,
+            Prober::Simulated(prober) => prober.probe(sent_at),
+            Prober::Snmp(prober) => prober.probe(sent_at).await,
+            Prober::Ntp(prober) => prober.probe().await,
+            Prober::Grpc(prober) => prober.probe().await,
+            Prober::WebSocket(prober) => prober.probe().await,
+            Prober::TcpBanner(prober) => prober.probe().await,
+            #[cfg(target_os = "linux")]
+            Prober::Ecmp(prober) => prober.probe().await,
+        }
+    }
+}
 
-    // Create ping client
-    let config = Config::default();
-    let ping_client = match PingClient::new(&config) {
-        Ok(client) => client,
+pub async fn run_prober(
+    target: Target,
+    labels: BTreeMap<String, String>,
+    client: Client,
+    bucket: String,
+    tx: broadcast::Sender<String>,
+    maintenance: Arc<Maintenance>,
+    diagnostics: Arc<Diagnostics>,
+) {
+    let mut interval = time::interval(Duration::from_secs(target.effective_probe_interval_secs() as u64));
+
+    let mut prober = match Prober::new(&target).await {
+        Ok(prober) => prober,
         Err(e) => {
-            eprintln!("Failed to create ping client for target {}: {}", target.id, e);
+            eprintln!("{}", e);
+            diagnostics.prober_failed(target.id, e);
             return;
         }
     };
 
-    // Parse host IP once and handle error
-    let host_ip: IpAddr = match target.host.parse() {
-        Ok(ip) => ip,
+    let schedule = match target.schedule.as_ref().map(crate::schedule::ProbeSchedule::parse).transpose() {
+        Ok(schedule) => schedule,
         Err(e) => {
-            eprintln!("Invalid IP address '{}' for target {}: {}", target.host, target.id, e);
+            eprintln!("Invalid schedule for target {}: {}", target.id, e);
+            diagnostics.prober_failed(target.id, format!("Invalid schedule: {}", e));
             return;
         }
     };
 
+    let mut paused = false;
+
     loop {
         interval.tick().await;
 
-        // Create pinger and perform ping
-        let mut pinger = ping_client.pinger(host_ip, PingIdentifier(0)).await;
-        let result = pinger.ping(PingSequence(0), &[0u8; 64]).await;
+        // Stamp the round with the send time so late or retried writes keep their real position
+        let sent_at = chrono::Utc::now();
 
-        let (is_lost, rtt) = match result {
-            Ok((_, duration)) => (false, duration.as_millis() as f64),
-            Err(_) => (true, 0.0),
-        };
+        // Skip rounds entirely during maintenance so they never count as loss
+        if maintenance.is_paused(target.id, sent_at) != paused {
+            paused = !paused;
+            println!("Probing of target {} {} for maintenance", target.id, if paused { "paused" } else { "resumed" });
+        }
+        if paused {
+            continue;
+        }
 
-        let point = match DataPoint::builder("probe_data")
-            .tag("target_id", target.id.to_string())
-            .tag("is_lost", is_lost.to_string())
-            .field("rtt_ms", rtt)
-            .build()
-        {
-            Ok(point) => point,
-            Err(e) => {
-                eprintln!("Failed to build data point for target {}: {}", target.id, e);
-                continue;
-            }
-        };
-
-        if let Err(e) = client.write(&bucket, futures::stream::iter(vec![point])).await {
-            eprintln!("Failed to write to InfluxDB: {}", e);
+        // Out-of-schedule periods are not measured at all rather than recorded as loss
+        if schedule.as_ref().is_some_and(|schedule| !schedule.allows(sent_at)) {
+            continue;
         }
 
-        let ws_msg = json!({ "target_id": target.id, "is_lost": is_lost, "rtt_ms": rtt }).to_string();
-        if let Err(_) = tx.send(ws_msg) {
-            // eprintln!("Failed to send WebSocket message: {}", e);
+        let outcome = prober.probe(sent_at).await;
+        if let Err(e) = record_round(&target, &labels, &client, &bucket, &tx, sent_at, outcome).await {
+            eprintln!("{}", e);
         }
     }
 }
+
+/// Runs a single round for `target` right away, outside of its interval, schedule
+/// and maintenance windows, and records it like any scheduled round.
+pub async fn probe_once(
+    target: &Target,
+    labels: &BTreeMap<String, String>,
+    client: &Client,
+    bucket: &str,
+    tx: &broadcast::Sender<String>,
+) -> Result<serde_json::Value, String> {
+    let mut prober = Prober::new(target).await?;
+    let sent_at = chrono::Utc::now();
+    let outcome = prober.probe(sent_at).await;
+    record_round(target, labels, client, bucket, tx, sent_at, outcome).await
+}
+
+// Stores the round in InfluxDB and broadcasts it to WebSocket clients; returns the
+// broadcast message.
+async fn record_round(
+    target: &Target,
+    labels: &BTreeMap<String, String>,
+    client: &Client,
+    bucket: &str,
+    tx: &broadcast::Sender<String>,
+    sent_at: chrono::DateTime<chrono::Utc>,
+    outcome: ProbeOutcome,
+) -> Result<serde_json::Value, String> {
+    let timestamp_ns = sent_at.timestamp_nanos_opt().unwrap_or_default();
+
+    let mut builder = DataPoint::builder("probe_data")
+        .tag("target_id", target.id.to_string())
+        .tag("is_lost", outcome.is_lost.to_string())
+        .field("rtt_ms", outcome.rtt_ms)
+        .timestamp(timestamp_ns);
+    // Labels become tags so queries can group and filter by them
+    for (key, value) in labels {
+        builder = builder.tag(key, value);
+    }
+
+    let mut ws_msg = json!({
+        "target_id": target.id,
+        "time": sent_at.to_rfc3339_opts(chrono::SecondsFormat::Nanos, true),
+        "is_lost": outcome.is_lost,
+        "rtt_ms": outcome.rtt_ms,
+    });
+
+    let samples = if outcome.samples.is_empty() && !outcome.is_lost {
+        vec![outcome.rtt_ms]
+    } else {
+        outcome.samples
+    };
+    if let Some(sketch) = crate::sketch::encode(&samples) {
+        builder = builder.field("rtt_sketch", sketch);
+    }
+
+    for (name, value) in outcome.fields {
+        ws_msg[name.as_str()] = field_json(&value);
+        builder = builder.field(name, value);
+    }
+
+    let extra_points = outcome.extra_points.into_iter().map(|point| {
+        labels
+            .iter()
+            .fold(point, |point, (key, value)| point.tag(key, value))
+            .tag("target_id", target.id.to_string())
+            .timestamp(timestamp_ns)
+    });
+    let points = std::iter::once(builder)
+        .chain(extra_points)
+        .map(|point| point.build())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to build data point for target {}: {}", target.id, e))?;
+
+    if let Err(e) = client.write(bucket, futures::stream::iter(points)).await {
+        eprintln!("Failed to write to InfluxDB: {}", e);
+    }
+
+    // A send error only means no WebSocket client is currently subscribed
+    let _ = tx.send(ws_msg.to_string());
+
+    Ok(ws_msg)
+}
+
+pub fn field_json(value: &FieldValue) -> serde_json::Value {
+    match value {
+        FieldValue::Bool(b) => json!(b),
+        FieldValue::F64(f) => json!(f),
+        FieldValue::I64(i) => json!(i),
+        FieldValue::String(s) => json!(s),
+    }
+}
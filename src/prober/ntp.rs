@@ -0,0 +1,217 @@
+use super::ProbeOutcome;
+use crate::models::target::Model as Target;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+// Seconds between the NTP era (1900) and the Unix epoch
+const NTP_UNIX_OFFSET_SECS: i64 = 2_208_988_800;
+
+/// Settings for the `ntp` probe type, read from `probe_params`.
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct NtpParams {
+    pub port: u16,
+    pub timeout_ms: u64,
+}
+
+impl Default for NtpParams {
+    fn default() -> Self {
+        NtpParams { port: 123, timeout_ms: 2000 }
+    }
+}
+
+pub struct NtpProber {
+    target_id: i32,
+    host: String,
+    port: u16,
+    timeout: Duration,
+}
+
+struct NtpResponse {
+    leap: u8,
+    stratum: u8,
+    reference_id: [u8; 4],
+    originate: u64,
+    receive: u64,
+    transmit: u64,
+}
+
+impl NtpProber {
+    pub fn new(target: &Target) -> Result<Self, String> {
+        let params: NtpParams = match &target.probe_params {
+            Some(params) => serde_json::from_value(params.clone())
+                .map_err(|e| format!("Invalid NTP probe parameters for target {}: {}", target.id, e))?,
+            None => NtpParams::default(),
+        };
+
+        Ok(NtpProber {
+            target_id: target.id,
+            host: target.host.clone(),
+            port: params.port,
+            timeout: Duration::from_millis(params.timeout_ms),
+        })
+    }
+
+    pub async fn probe(&mut self) -> ProbeOutcome {
+        match tokio::time::timeout(self.timeout, self.query()).await {
+            Ok(Ok(outcome)) => outcome,
+            Ok(Err(e)) => {
+                eprintln!("NTP query to target {} failed: {}", self.target_id, e);
+                ProbeOutcome::lost()
+            }
+            Err(_) => ProbeOutcome::lost(),
+        }
+    }
+
+    async fn query(&self) -> Result<ProbeOutcome, String> {
+        // Resolved every round, binding to the unspecified address of the family found
+        let destination = tokio::net::lookup_host((self.host.as_str(), self.port))
+            .await
+            .map_err(|e| e.to_string())?
+            .next()
+            .ok_or_else(|| format!("no address found for '{}'", self.host))?;
+        let local: SocketAddr = match destination {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local).await.map_err(|e| e.to_string())?;
+        socket.connect(destination).await.map_err(|e| e.to_string())?;
+
+        // Client mode (3), version 4, no leap indicator
+        let mut request = [0u8; 48];
+        request[0] = (4 << 3) | 3;
+        let t1 = Utc::now();
+        let t1_ntp = to_ntp_timestamp(t1);
+        request[40..48].copy_from_slice(&t1_ntp.to_be_bytes());
+        socket.send(&request).await.map_err(|e| e.to_string())?;
+
+        let mut buf = [0u8; 512];
+        let len = socket.recv(&mut buf).await.map_err(|e| e.to_string())?;
+        let t4 = Utc::now();
+        let response = parse_response(&buf[..len]).ok_or("malformed NTP response")?;
+        if response.originate != t1_ntp {
+            return Err("response does not match the request".to_string());
+        }
+
+        let mut outcome = if response.stratum == 0 {
+            // Kiss-o'-Death: the reference id carries an ASCII code such as RATE or DENY
+            let mut outcome = ProbeOutcome::lost();
+            outcome.field("ntp_failure", format!("kiss-o-death {}", ascii_reference(&response.reference_id)));
+            outcome
+        } else if response.leap == 3 || response.stratum >= 16 {
+            let mut outcome = ProbeOutcome::lost();
+            outcome.field("ntp_failure", "unsynchronised");
+            outcome
+        } else {
+            let t2 = from_ntp_timestamp(response.receive);
+            let t3 = from_ntp_timestamp(response.transmit);
+            let delay_ms = ((t4 - t1) - (t3 - t2)).num_microseconds().unwrap_or_default() as f64 / 1000.0;
+            let offset_ms = (((t2 - t1) + (t3 - t4)).num_microseconds().unwrap_or_default() as f64 / 2.0) / 1000.0;
+
+            let mut outcome = ProbeOutcome::reply(delay_ms.max(0.0));
+            outcome.field("ntp_offset_ms", offset_ms);
+            outcome
+        };
+
+        outcome.field("ntp_stratum", response.stratum as i64);
+        outcome.field("ntp_leap", response.leap as i64);
+        outcome.field("ntp_reference_id", reference_id(response.stratum, &response.reference_id));
+        Ok(outcome)
+    }
+}
+
+fn parse_response(buf: &[u8]) -> Option<NtpResponse> {
+    if buf.len() < 48 {
+        return None;
+    }
+    // Only accept server (4) or broadcast (5) mode packets
+    let mode = buf[0] & 0x07;
+    if mode != 4 && mode != 5 {
+        return None;
+    }
+    let timestamp = |offset: usize| u64::from_be_bytes(buf[offset..offset + 8].try_into().unwrap());
+
+    Some(NtpResponse {
+        leap: buf[0] >> 6,
+        stratum: buf[1],
+        reference_id: buf[12..16].try_into().unwrap(),
+        originate: timestamp(24),
+        receive: timestamp(32),
+        transmit: timestamp(40),
+    })
+}
+
+fn to_ntp_timestamp(time: DateTime<Utc>) -> u64 {
+    let secs = (time.timestamp() + NTP_UNIX_OFFSET_SECS) as u64;
+    let fraction = ((time.timestamp_subsec_nanos() as u64) << 32) / 1_000_000_000;
+    (secs << 32) | fraction
+}
+
+// NTP seconds wrap every 2^32 seconds, the first time on 2036-02-07. As in RFC 4330, values with
+// the top bit clear are taken to be in the next era, which covers 1968 to 2104.
+fn from_ntp_timestamp(timestamp: u64) -> DateTime<Utc> {
+    let ntp_secs = timestamp >> 32;
+    let ntp_secs = if ntp_secs & 0x8000_0000 == 0 { ntp_secs + (1 << 32) } else { ntp_secs };
+    let secs = ntp_secs as i64 - NTP_UNIX_OFFSET_SECS;
+    let nanos = ((timestamp & 0xffff_ffff) * 1_000_000_000) >> 32;
+    DateTime::from_timestamp(secs, nanos as u32).unwrap_or_default()
+}
+
+fn ascii_reference(reference_id: &[u8; 4]) -> String {
+    reference_id
+        .iter()
+        .take_while(|b| **b != 0)
+        .map(|b| *b as char)
+        .collect()
+}
+
+// Primary servers name their clock source, secondary servers the IPv4 address of their upstream
+fn reference_id(stratum: u8, reference_id: &[u8; 4]) -> String {
+    if stratum <= 1 {
+        ascii_reference(reference_id)
+    } else {
+        std::net::Ipv4Addr::from(*reference_id).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn timestamps_convert_both_ways() {
+        let time = at("2025-03-01T12:34:56.789Z");
+        let timestamp = to_ntp_timestamp(time);
+        assert_eq!(timestamp >> 32, 3_949_821_296);
+        // The 32-bit fraction is finer than a nanosecond, but truncation can lose one
+        let back = from_ntp_timestamp(timestamp);
+        assert!((time - back).num_nanoseconds().unwrap().abs() <= 1);
+    }
+
+    #[test]
+    fn timestamps_cross_the_era_boundary() {
+        // The last second of era 0 and the first of era 1
+        let last = at("2036-02-07T06:28:15Z");
+        let first = at("2036-02-07T06:28:16Z");
+        assert_eq!(to_ntp_timestamp(last) >> 32, 0xffff_ffff);
+        assert_eq!(to_ntp_timestamp(first) >> 32, 0);
+        assert_eq!(from_ntp_timestamp(to_ntp_timestamp(last)), last);
+        assert_eq!(from_ntp_timestamp(to_ntp_timestamp(first)), first);
+
+        let later = at("2040-06-01T00:00:00Z");
+        assert_eq!(from_ntp_timestamp(to_ntp_timestamp(later)), later);
+    }
+
+    #[test]
+    fn reference_ids_depend_on_the_stratum() {
+        assert_eq!(reference_id(1, b"GPS\0"), "GPS");
+        assert_eq!(reference_id(2, &[192, 0, 2, 1]), "192.0.2.1");
+    }
+}
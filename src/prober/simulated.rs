@@ -0,0 +1,130 @@
+use super::ProbeOutcome;
+use crate::models::target::Model as Target;
+use chrono::{DateTime, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::{Distribution, Exp, Normal, Pareto};
+use serde::Deserialize;
+
+/// Latency/loss model for the `simulated` probe type, read from `probe_params`.
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct SimulatedParams {
+    pub base_latency_ms: f64,
+    pub jitter: Jitter,
+    pub loss_rate: f64,
+    /// Fixed seed for reproducible series; combined with the target id so targets differ
+    pub seed: Option<u64>,
+    pub degradations: Vec<DegradationWindow>,
+}
+
+impl Default for SimulatedParams {
+    fn default() -> Self {
+        SimulatedParams {
+            base_latency_ms: 20.0,
+            jitter: Jitter::Normal { stddev_ms: 2.0 },
+            loss_rate: 0.0,
+            seed: None,
+            degradations: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "distribution", rename_all = "lowercase")]
+pub enum Jitter {
+    None,
+    Uniform { max_ms: f64 },
+    Normal { stddev_ms: f64 },
+    Exponential { mean_ms: f64 },
+    Pareto { scale_ms: f64, shape: f64 },
+}
+
+/// A period of worse service, optionally repeating every `every_secs` from `start`.
+#[derive(Deserialize, Debug)]
+pub struct DegradationWindow {
+    pub start: DateTime<Utc>,
+    pub duration_secs: u64,
+    pub every_secs: Option<u64>,
+    #[serde(default)]
+    pub extra_latency_ms: f64,
+    /// Replaces the base loss rate while the window is active
+    pub loss_rate: Option<f64>,
+}
+
+impl DegradationWindow {
+    fn is_active(&self, at: DateTime<Utc>) -> bool {
+        let elapsed = (at - self.start).num_seconds();
+        if elapsed < 0 {
+            return false;
+        }
+        let elapsed = match self.every_secs {
+            Some(every) if every > 0 => elapsed as u64 % every,
+            _ => elapsed as u64,
+        };
+        elapsed < self.duration_secs
+    }
+}
+
+pub struct SimulatedProber {
+    params: SimulatedParams,
+    rng: StdRng,
+}
+
+impl SimulatedProber {
+    pub fn new(target: &Target) -> Result<Self, String> {
+        let params: SimulatedParams = match &target.probe_params {
+            Some(params) => serde_json::from_value(params.clone())
+                .map_err(|e| format!("Invalid simulated probe parameters for target {}: {}", target.id, e))?,
+            None => SimulatedParams::default(),
+        };
+
+        let rng = match params.seed {
+            Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(target.id as u64)),
+            None => StdRng::from_entropy(),
+        };
+
+        Ok(SimulatedProber { params, rng })
+    }
+
+    pub fn probe(&mut self, sent_at: DateTime<Utc>) -> ProbeOutcome {
+        let mut loss_rate = self.params.loss_rate;
+        let mut extra_latency_ms = 0.0;
+        let mut degraded = false;
+        for window in self.params.degradations.iter().filter(|w| w.is_active(sent_at)) {
+            degraded = true;
+            extra_latency_ms += window.extra_latency_ms;
+            if let Some(rate) = window.loss_rate {
+                loss_rate = rate;
+            }
+        }
+
+        // Always draw the jitter so lost rounds do not shift the rest of a seeded series
+        let jitter_ms = self.jitter_sample();
+        let mut outcome = if self.rng.gen_bool(loss_rate.clamp(0.0, 1.0)) {
+            ProbeOutcome::lost()
+        } else {
+            ProbeOutcome::reply((self.params.base_latency_ms + extra_latency_ms + jitter_ms).max(0.0))
+        };
+        outcome.field("degraded", degraded);
+        outcome
+    }
+
+    fn jitter_sample(&mut self) -> f64 {
+        match self.params.jitter {
+            Jitter::None => 0.0,
+            Jitter::Uniform { max_ms } if max_ms > 0.0 => self.rng.gen_range(0.0..max_ms),
+            Jitter::Normal { stddev_ms } => Normal::new(0.0, stddev_ms)
+                .map(|d| d.sample(&mut self.rng))
+                .unwrap_or(0.0),
+            Jitter::Exponential { mean_ms } if mean_ms > 0.0 => Exp::new(1.0 / mean_ms)
+                .map(|d| d.sample(&mut self.rng))
+                .unwrap_or(0.0),
+            // Pareto samples start at the scale; shift so the minimum adds no latency
+            Jitter::Pareto { scale_ms, shape } => Pareto::new(scale_ms, shape)
+                .map(|d| d.sample(&mut self.rng) - scale_ms)
+                .unwrap_or(0.0),
+            _ => 0.0,
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use super::ProbeOutcome;
+use crate::models::target::Model as Target;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use snmp2::{AsyncSession, Oid, Value};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// IF-MIB columns polled for every entry of `interfaces`, using the 64-bit counters where they exist
+const INTERFACE_COUNTERS: [(&str, &str); 6] = [
+    ("in_octets", "1.3.6.1.2.1.31.1.1.1.6"),
+    ("out_octets", "1.3.6.1.2.1.31.1.1.1.10"),
+    ("in_errors", "1.3.6.1.2.1.2.2.1.14"),
+    ("out_errors", "1.3.6.1.2.1.2.2.1.20"),
+    ("in_discards", "1.3.6.1.2.1.2.2.1.13"),
+    ("out_discards", "1.3.6.1.2.1.2.2.1.19"),
+];
+
+/// Settings for the `snmp` probe type, read from `probe_params`.
+#[derive(Deserialize, Debug)]
+pub struct SnmpParams {
+    #[serde(default = "default_version")]
+    pub version: String,
+    #[serde(default = "default_community")]
+    pub community: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    /// ifIndex values whose octet, error and discard counters are polled
+    #[serde(default)]
+    pub interfaces: Vec<u32>,
+    #[serde(default)]
+    pub oids: Vec<SnmpOid>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SnmpOid {
+    pub name: String,
+    pub oid: String,
+}
+
+fn default_version() -> String {
+    "2c".to_string()
+}
+
+fn default_community() -> String {
+    "public".to_string()
+}
+
+fn default_port() -> u16 {
+    161
+}
+
+fn default_timeout_ms() -> u64 {
+    2000
+}
+
+struct PolledOid {
+    name: String,
+    oid: Vec<u64>,
+}
+
+pub struct SnmpProber {
+    target_id: i32,
+    destination: String,
+    community: String,
+    timeout: Duration,
+    oids: Vec<PolledOid>,
+    session: Option<Box<AsyncSession>>,
+    // Last counter reading per OID name, to turn counters into rates
+    last_counters: HashMap<String, (u64, DateTime<Utc>)>,
+}
+
+impl SnmpProber {
+    pub fn new(target: &Target) -> Result<Self, String> {
+        let params: SnmpParams = target
+            .probe_params
+            .clone()
+            .ok_or_else(|| format!("Missing SNMP probe parameters for target {}", target.id))
+            .and_then(|params| {
+                serde_json::from_value(params)
+                    .map_err(|e| format!("Invalid SNMP probe parameters for target {}: {}", target.id, e))
+            })?;
+
+        if params.version != "2c" {
+            return Err(format!(
+                "Unsupported SNMP version '{}' for target {}, only 2c is supported",
+                params.version, target.id
+            ));
+        }
+
+        let mut oids = Vec::new();
+        for index in &params.interfaces {
+            for (name, column) in INTERFACE_COUNTERS {
+                oids.push((format!("if{}_{}", index, name), format!("{}.{}", column, index)));
+            }
+        }
+        oids.extend(params.oids.into_iter().map(|o| (o.name, o.oid)));
+        if oids.is_empty() {
+            return Err(format!("No SNMP OIDs or interfaces configured for target {}", target.id));
+        }
+
+        let oids = oids
+            .into_iter()
+            .map(|(name, oid)| {
+                parse_oid(&oid)
+                    .map(|oid| PolledOid { name, oid })
+                    .ok_or_else(|| format!("Invalid OID '{}' for target {}", oid, target.id))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(SnmpProber {
+            target_id: target.id,
+            destination: super::join_host_port(&target.host, params.port),
+            community: params.community,
+            timeout: Duration::from_millis(params.timeout_ms),
+            oids,
+            session: None,
+            last_counters: HashMap::new(),
+        })
+    }
+
+    pub async fn probe(&mut self, sent_at: DateTime<Utc>) -> ProbeOutcome {
+        let started = Instant::now();
+        let values = match self.poll().await {
+            Ok(values) => values,
+            Err(e) => {
+                eprintln!("SNMP poll of target {} failed: {}", self.target_id, e);
+                // Start from a fresh socket next round
+                self.session = None;
+                return ProbeOutcome::lost();
+            }
+        };
+
+        let mut outcome = ProbeOutcome::reply(started.elapsed().as_secs_f64() * 1000.0);
+        for (name, value) in values {
+            match value {
+                SnmpReading::Counter { value, bits } => {
+                    if let Some(rate) = self.counter_rate(&name, value, bits, sent_at) {
+                        outcome.field(format!("snmp_{}_per_sec", name), rate);
+                    }
+                }
+                SnmpReading::Gauge(value) => outcome.field(format!("snmp_{}", name), value),
+            }
+        }
+        outcome
+    }
+
+    async fn poll(&mut self) -> Result<Vec<(String, SnmpReading)>, String> {
+        if self.session.is_none() {
+            let session = AsyncSession::new_v2c(self.destination.as_str(), self.community.as_bytes(), 0)
+                .await
+                .map_err(|e| format!("failed to open session to {}: {}", self.destination, e))?;
+            self.session = Some(Box::new(session));
+        }
+        let session = self.session.as_mut().unwrap();
+
+        let oids = self
+            .oids
+            .iter()
+            .map(|o| Oid::from(&o.oid).map_err(|e| format!("invalid OID: {:?}", e)))
+            .collect::<Result<Vec<_>, String>>()?;
+        let oid_refs: Vec<&Oid> = oids.iter().collect();
+
+        let response = tokio::time::timeout(self.timeout, session.get_many(&oid_refs))
+            .await
+            .map_err(|_| "request timed out".to_string())?
+            .map_err(|e| format!("request failed: {:?}", e))?;
+        if response.error_status != 0 {
+            return Err(format!(
+                "agent returned error status {} at index {}",
+                response.error_status, response.error_index
+            ));
+        }
+
+        // Varbinds come back in request order
+        let mut values = Vec::new();
+        for (polled, (_, value)) in self.oids.iter().zip(response.varbinds) {
+            let reading = match value {
+                Value::Counter32(v) => SnmpReading::Counter { value: v as u64, bits: 32 },
+                Value::Counter64(v) => SnmpReading::Counter { value: v, bits: 64 },
+                Value::Integer(v) => SnmpReading::Gauge(v),
+                Value::Unsigned32(v) | Value::Timeticks(v) => SnmpReading::Gauge(v as i64),
+                // noSuchObject/noSuchInstance or a type we cannot chart
+                _ => continue,
+            };
+            values.push((polled.name.clone(), reading));
+        }
+        Ok(values)
+    }
+
+    fn counter_rate(&mut self, name: &str, value: u64, bits: u32, at: DateTime<Utc>) -> Option<f64> {
+        let previous = self.last_counters.insert(name.to_string(), (value, at))?;
+        let (last_value, last_at) = previous;
+        let elapsed = (at - last_at).num_milliseconds() as f64 / 1000.0;
+        if elapsed <= 0.0 {
+            return None;
+        }
+
+        let delta = if value >= last_value {
+            value - last_value
+        } else if bits == 32 {
+            // Counter32 wrapped
+            value + (1u64 << 32) - last_value
+        } else {
+            // A Counter64 going backwards means the agent restarted, skip this interval
+            return None;
+        };
+        Some(delta as f64 / elapsed)
+    }
+}
+
+enum SnmpReading {
+    Counter { value: u64, bits: u32 },
+    Gauge(i64),
+}
+
+fn parse_oid(oid: &str) -> Option<Vec<u64>> {
+    oid.trim_start_matches('.')
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prober() -> SnmpProber {
+        SnmpProber {
+            target_id: 1,
+            destination: "192.0.2.1:161".to_string(),
+            community: "public".to_string(),
+            timeout: Duration::from_secs(1),
+            oids: Vec::new(),
+            session: None,
+            last_counters: HashMap::new(),
+        }
+    }
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    #[test]
+    fn counters_become_rates_from_the_second_reading() {
+        let mut prober = prober();
+        assert_eq!(prober.counter_rate("in", 1_000, 64, at(0)), None);
+        assert_eq!(prober.counter_rate("in", 3_000, 64, at(10)), Some(200.0));
+        // Readings at the same time give no rate, but replace the last one
+        assert_eq!(prober.counter_rate("in", 3_500, 64, at(10)), None);
+        assert_eq!(prober.counter_rate("in", 4_500, 64, at(20)), Some(100.0));
+    }
+
+    #[test]
+    fn counter32_wraps_around() {
+        let mut prober = prober();
+        prober.counter_rate("in", u32::MAX as u64 - 99, 32, at(0));
+        assert_eq!(prober.counter_rate("in", 100, 32, at(10)), Some(20.0));
+    }
+
+    #[test]
+    fn counter64_going_backwards_is_skipped() {
+        let mut prober = prober();
+        prober.counter_rate("in", 5_000, 64, at(0));
+        assert_eq!(prober.counter_rate("in", 1_000, 64, at(10)), None);
+        // The agent restarted; rates continue from the new reading
+        assert_eq!(prober.counter_rate("in", 2_000, 64, at(20)), Some(100.0));
+    }
+
+    #[test]
+    fn oids_parse_with_or_without_a_leading_dot() {
+        assert_eq!(parse_oid(".1.3.6.1.2.1.1.3.0"), Some(vec![1, 3, 6, 1, 2, 1, 1, 3, 0]));
+        assert_eq!(parse_oid("1.3.6"), Some(vec![1, 3, 6]));
+        assert_eq!(parse_oid("1.3.x"), None);
+    }
+}
@@ -0,0 +1,147 @@
+use super::ProbeOutcome;
+use crate::models::target::Model as Target;
+use regex::Regex;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+// Stop reading once this much data arrived without a match
+const MAX_BANNER_BYTES: usize = 4096;
+
+/// Settings for the `tcp-banner` probe type, read from `probe_params`.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct TcpBannerParams {
+    /// Appended to `host` when set, otherwise `host` must already be `host:port`
+    pub port: Option<u16>,
+    /// Payload written right after connecting, e.g. "PING\r\n"
+    pub send: Option<String>,
+    /// Regex the banner or reply must match; any data counts when unset
+    pub expect: Option<String>,
+    pub timeout_ms: Option<u64>,
+    /// Only time the TCP handshake; `send` and `expect` are ignored
+    pub connect_only: bool,
+}
+
+pub struct TcpBannerProber {
+    address: String,
+    send: Option<String>,
+    expect: Option<Regex>,
+    timeout: Duration,
+    connect_only: bool,
+}
+
+enum BannerFailure {
+    Connect,
+    Closed,
+    Mismatch,
+    Timeout,
+}
+
+impl BannerFailure {
+    fn as_str(&self) -> &'static str {
+        match self {
+            BannerFailure::Connect => "connect",
+            BannerFailure::Closed => "closed",
+            BannerFailure::Mismatch => "mismatch",
+            BannerFailure::Timeout => "timeout",
+        }
+    }
+}
+
+impl TcpBannerProber {
+    pub fn new(target: &Target) -> Result<Self, String> {
+        let params: TcpBannerParams = match &target.probe_params {
+            Some(params) => serde_json::from_value(params.clone())
+                .map_err(|e| format!("Invalid TCP banner probe parameters for target {}: {}", target.id, e))?,
+            None => TcpBannerParams::default(),
+        };
+
+        let address = match params.port {
+            Some(port) => super::join_host_port(&target.host, port),
+            None => target.host.clone(),
+        };
+        let expect = params
+            .expect
+            .map(|pattern| Regex::new(&pattern))
+            .transpose()
+            .map_err(|e| format!("Invalid banner pattern for target {}: {}", target.id, e))?;
+
+        Ok(TcpBannerProber {
+            address,
+            send: params.send,
+            expect,
+            timeout: Duration::from_millis(params.timeout_ms.unwrap_or(5000)),
+            connect_only: params.connect_only,
+        })
+    }
+
+    pub async fn probe(&mut self) -> ProbeOutcome {
+        let started = Instant::now();
+        let deadline = tokio::time::Instant::from_std(started + self.timeout);
+
+        let mut stream = match tokio::time::timeout_at(deadline, TcpStream::connect(&self.address)).await {
+            Ok(Ok(stream)) => stream,
+            Ok(Err(_)) => return failed(BannerFailure::Connect, None),
+            Err(_) => return failed(BannerFailure::Timeout, None),
+        };
+        let connect_ms = started.elapsed().as_secs_f64() * 1000.0;
+        if self.connect_only {
+            let mut outcome = ProbeOutcome::reply(connect_ms);
+            outcome.field("tcp_connect_ms", connect_ms);
+            return outcome;
+        }
+
+        let connected = Instant::now();
+        match tokio::time::timeout_at(deadline, self.read_banner(&mut stream)).await {
+            Ok(Ok(banner)) => {
+                let banner_ms = connected.elapsed().as_secs_f64() * 1000.0;
+                let mut outcome = ProbeOutcome::reply(connect_ms + banner_ms);
+                outcome.field("tcp_connect_ms", connect_ms);
+                outcome.field("tcp_banner_ms", banner_ms);
+                outcome.field("tcp_banner", banner);
+                outcome
+            }
+            Ok(Err(failure)) => failed(failure, Some(connect_ms)),
+            Err(_) => failed(BannerFailure::Timeout, Some(connect_ms)),
+        }
+    }
+
+    async fn read_banner(&self, stream: &mut TcpStream) -> Result<String, BannerFailure> {
+        if let Some(payload) = &self.send {
+            stream.write_all(payload.as_bytes()).await.map_err(|_| BannerFailure::Closed)?;
+        }
+
+        let mut received = Vec::new();
+        let mut buf = [0u8; 1024];
+        loop {
+            let n = stream.read(&mut buf).await.map_err(|_| BannerFailure::Closed)?;
+            if n == 0 {
+                return Err(if received.is_empty() { BannerFailure::Closed } else { BannerFailure::Mismatch });
+            }
+            received.extend_from_slice(&buf[..n]);
+
+            let text = String::from_utf8_lossy(&received);
+            let matched = match &self.expect {
+                Some(pattern) => pattern.find(&text).map(|m| m.as_str().to_string()),
+                None => Some(text.lines().next().unwrap_or_default().to_string()),
+            };
+            if let Some(banner) = matched {
+                return Ok(banner);
+            }
+            if received.len() >= MAX_BANNER_BYTES {
+                return Err(BannerFailure::Mismatch);
+            }
+        }
+    }
+}
+
+fn failed(failure: BannerFailure, connect_ms: Option<f64>) -> ProbeOutcome {
+    let mut outcome = ProbeOutcome::lost();
+    outcome.field("tcp_failure", failure.as_str());
+    if let Some(connect_ms) = connect_ms {
+        outcome.field("tcp_connect_ms", connect_ms);
+    }
+    outcome
+}
@@ -0,0 +1,115 @@
+use super::ProbeOutcome;
+use crate::models::target::Model as Target;
+use futures::{SinkExt, StreamExt};
+use regex::Regex;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::Message;
+
+/// Settings for the `websocket` probe type, read from `probe_params`.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct WebSocketParams {
+    /// ws:// or wss:// URL; defaults to `host` when unset
+    pub url: Option<String>,
+    /// Text message sent once the connection is upgraded
+    pub message: Option<String>,
+    /// Regex a reply must match; any reply counts when unset
+    pub expect: Option<String>,
+    pub timeout_ms: Option<u64>,
+}
+
+pub struct WebSocketProber {
+    target_id: i32,
+    url: String,
+    message: Option<String>,
+    expect: Option<Regex>,
+    timeout: Duration,
+}
+
+struct WebSocketTimings {
+    upgrade_ms: f64,
+    message_rtt_ms: Option<f64>,
+}
+
+impl WebSocketProber {
+    pub fn new(target: &Target) -> Result<Self, String> {
+        let params: WebSocketParams = match &target.probe_params {
+            Some(params) => serde_json::from_value(params.clone())
+                .map_err(|e| format!("Invalid WebSocket probe parameters for target {}: {}", target.id, e))?,
+            None => WebSocketParams::default(),
+        };
+
+        let url = params.url.unwrap_or_else(|| target.host.clone());
+        if !url.starts_with("ws://") && !url.starts_with("wss://") {
+            return Err(format!("Invalid WebSocket URL '{}' for target {}", url, target.id));
+        }
+        let expect = params
+            .expect
+            .map(|pattern| Regex::new(&pattern))
+            .transpose()
+            .map_err(|e| format!("Invalid reply pattern for target {}: {}", target.id, e))?;
+
+        Ok(WebSocketProber {
+            target_id: target.id,
+            url,
+            message: params.message,
+            expect,
+            timeout: Duration::from_millis(params.timeout_ms.unwrap_or(5000)),
+        })
+    }
+
+    pub async fn probe(&mut self) -> ProbeOutcome {
+        let started = Instant::now();
+        match tokio::time::timeout(self.timeout, self.exchange()).await {
+            Ok(Ok(timings)) => {
+                let mut outcome = ProbeOutcome::reply(started.elapsed().as_secs_f64() * 1000.0);
+                outcome.field("ws_upgrade_ms", timings.upgrade_ms);
+                if let Some(message_rtt_ms) = timings.message_rtt_ms {
+                    outcome.field("ws_message_rtt_ms", message_rtt_ms);
+                }
+                outcome
+            }
+            Ok(Err(e)) => {
+                eprintln!("WebSocket probe of target {} failed: {}", self.target_id, e);
+                ProbeOutcome::lost()
+            }
+            Err(_) => ProbeOutcome::lost(),
+        }
+    }
+
+    async fn exchange(&self) -> Result<WebSocketTimings, String> {
+        let started = Instant::now();
+        let (mut stream, _) = tokio_tungstenite::connect_async(self.url.as_str())
+            .await
+            .map_err(|e| format!("upgrade failed: {}", e))?;
+        let upgrade_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+        let mut message_rtt_ms = None;
+        if let Some(message) = &self.message {
+            let sent = Instant::now();
+            stream
+                .send(Message::Text(message.clone()))
+                .await
+                .map_err(|e| format!("send failed: {}", e))?;
+
+            // Skip control frames and unrelated pushes until the expected reply shows up
+            loop {
+                let reply = match stream.next().await {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Binary(data))) => String::from_utf8_lossy(&data).into_owned(),
+                    Some(Ok(Message::Close(_))) | None => return Err("connection closed before reply".to_string()),
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => return Err(format!("receive failed: {}", e)),
+                };
+                if self.expect.as_ref().is_none_or(|pattern| pattern.is_match(&reply)) {
+                    message_rtt_ms = Some(sent.elapsed().as_secs_f64() * 1000.0);
+                    break;
+                }
+            }
+        }
+
+        let _ = stream.close(None).await;
+        Ok(WebSocketTimings { upgrade_ms, message_rtt_ms })
+    }
+}
@@ -0,0 +1,612 @@
+//! Changes to many targets at once: bulk operations and CSV/JSON import, each
+//! validated up front and applied in one transaction, and CSV/JSON export.
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use sea_orm::sea_query::Expr;
+use sea_orm::{ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use crate::models::target;
+use crate::models::target_revision::{self, Action};
+use crate::routes::error::{ApiError, ApiJson, ApiQuery, FieldError};
+use crate::routes::history::Actor;
+use crate::routes::import::ImportQuery;
+use crate::routes::targets::{self, TargetInput, TargetQuery, TargetWithLabels};
+use crate::AppState;
+
+#[derive(Deserialize, Debug)]
+pub struct TargetUpdate {
+    pub id: i32,
+    #[serde(flatten)]
+    pub target: TargetInput,
+}
+
+/// Operations are applied in the order of the fields; a target may appear in only one of them.
+#[derive(Deserialize, Debug, Default)]
+pub struct BulkRequest {
+    #[serde(default)]
+    pub create: Vec<TargetInput>,
+    #[serde(default)]
+    pub update: Vec<TargetUpdate>,
+    #[serde(default)]
+    pub activate: Vec<i32>,
+    #[serde(default)]
+    pub deactivate: Vec<i32>,
+    #[serde(default)]
+    pub delete: Vec<i32>,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct BulkReport {
+    pub dry_run: bool,
+    pub created: Vec<TargetWithLabels>,
+    pub updated: Vec<TargetWithLabels>,
+    pub activated: Vec<i32>,
+    pub deactivated: Vec<i32>,
+    pub deleted: Vec<i32>,
+}
+
+/// One row of a CSV import or export. Labels, probe parameters and schedules are written as JSON,
+/// so any label value survives the trip.
+#[derive(Deserialize, Serialize, Debug)]
+struct CsvRecord {
+    #[serde(default)]
+    id: Option<i32>,
+    name: String,
+    host: String,
+    #[serde(default)]
+    probe_type: Option<String>,
+    #[serde(default)]
+    probe_interval_secs: Option<i32>,
+    #[serde(default)]
+    is_active: Option<bool>,
+    #[serde(default)]
+    group_id: Option<i32>,
+    #[serde(default)]
+    profile_id: Option<i32>,
+    #[serde(default)]
+    labels: Option<String>,
+    #[serde(default)]
+    probe_params: Option<String>,
+    #[serde(default)]
+    schedule: Option<String>,
+}
+
+/// A target of a JSON import; with an `id` it updates that target, otherwise it creates one.
+#[derive(Deserialize, Debug)]
+struct JsonRecord {
+    #[serde(default)]
+    id: Option<i32>,
+    #[serde(flatten)]
+    target: TargetInput,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ExportQuery {
+    /// `csv` (default) or `json`
+    pub format: Option<String>,
+    pub group_id: Option<i32>,
+    pub selector: Option<String>,
+}
+
+// Checks the whole request against the database and itself; `field` names the n-th entry of an operation
+async fn validate(
+    db: &DatabaseConnection,
+    request: &BulkRequest,
+    field: &(dyn Fn(&str, usize) -> String + Sync),
+) -> Result<(), ApiError> {
+    let mut fields = Vec::new();
+
+    // Everything the checks need is loaded up front, so large requests cost a few queries
+    let inherited = target::Inherited::load(db)
+        .await
+        .map_err(ApiError::database("Unable to check target groups and profiles"))?;
+    let ids: Vec<i32> = request
+        .update
+        .iter()
+        .map(|update| update.id)
+        .chain(request.activate.iter().chain(&request.deactivate).chain(&request.delete).copied())
+        .collect();
+    let existing: HashMap<i32, target::Model> = target::Entity::find()
+        .filter(Condition::any().add(target::Column::DeletedAt.is_null()).add(target::Column::Id.is_in(ids)))
+        .all(db)
+        .await
+        .map_err(ApiError::database("Unable to find targets"))?
+        .into_iter()
+        .map(|target| (target.id, target))
+        .collect();
+
+    // Every target may be changed once, and the targets file owns the targets it created
+    let mut changed: HashMap<i32, String> = HashMap::new();
+    let mut check_id = |name: String, id: i32, fields: &mut Vec<FieldError>| -> bool {
+        let problem = match existing.get(&id) {
+            None => Some(format!("target {} does not exist", id)),
+            Some(target) if target.config_key.is_some() => Some(format!("target {} is managed by the targets file", id)),
+            Some(target) if target.deleted_at.is_some() => Some(format!("target {} is deleted", id)),
+            Some(_) => changed.get(&id).map(|previous| format!("target {} is already changed by {}", id, previous)),
+        };
+        match problem {
+            Some(problem) => {
+                fields.push(FieldError::new(name, problem));
+                false
+            }
+            None => {
+                changed.insert(id, name);
+                true
+            }
+        }
+    };
+
+    // Names must be unique within a group once the request is applied. Updated and deleted
+    // targets give up their current names, so names can be swapped or reused in one request.
+    let released: HashSet<i32> = request.update.iter().map(|update| update.id).chain(request.delete.iter().copied()).collect();
+    let kept: HashSet<(Option<i32>, &str)> = existing
+        .values()
+        .filter(|target| target.deleted_at.is_none() && !released.contains(&target.id))
+        .map(|target| (target.group_id, target.name.as_str()))
+        .collect();
+    let mut names: HashMap<(Option<i32>, String), String> = HashMap::new();
+    let mut check_name = |name: String, input: &TargetInput, fields: &mut Vec<FieldError>| {
+        if kept.contains(&(input.group_id, input.name.as_str())) {
+            fields.push(FieldError::new(format!("{}.name", name), targets::name_taken(input).message));
+        } else if let Some(previous) = names.insert((input.group_id, input.name.clone()), name.clone()) {
+            fields.push(FieldError::new(format!("{}.name", name), format!("'{}' is also used by {}", input.name, previous)));
+        }
+    };
+
+    for (i, input) in request.create.iter().enumerate() {
+        let name = field("create", i);
+        for e in targets::field_errors(input, &inherited) {
+            fields.push(FieldError::new(format!("{}.{}", name, e.field), e.message));
+        }
+        check_name(name, input, &mut fields);
+    }
+    for (i, update) in request.update.iter().enumerate() {
+        let name = field("update", i);
+        if !check_id(format!("{}.id", name), update.id, &mut fields) {
+            continue;
+        }
+        for e in targets::field_errors(&update.target, &inherited) {
+            fields.push(FieldError::new(format!("{}.{}", name, e.field), e.message));
+        }
+        check_name(name, &update.target, &mut fields);
+    }
+    for (operation, ids) in [("activate", &request.activate), ("deactivate", &request.deactivate), ("delete", &request.delete)] {
+        for (i, id) in ids.iter().enumerate() {
+            check_id(field(operation, i), *id, &mut fields);
+        }
+    }
+
+    if fields.is_empty() {
+        Ok(())
+    } else {
+        Err(ApiError::validation(fields))
+    }
+}
+
+// Records a revision for each of `ids` after a change made to all of them at once
+async fn record_revisions<C: ConnectionTrait>(db: &C, ids: &[i32], action: Action, actor: &str) -> Result<(), sea_orm::DbErr> {
+    for target in target::Entity::find().filter(target::Column::Id.is_in(ids.iter().copied())).all(db).await? {
+        target_revision::record(db, action, actor, &target).await?;
+    }
+    Ok(())
+}
+
+// Validates and applies `request` in one transaction, rolled back again for a dry run
+async fn apply(
+    state: &AppState,
+    request: BulkRequest,
+    dry_run: bool,
+    actor: &str,
+    field: &(dyn Fn(&str, usize) -> String + Sync),
+) -> Result<BulkReport, ApiError> {
+    validate(state.db.as_ref(), &request, field).await?;
+
+    let applied = async {
+        let txn = state.db.begin().await?;
+        let mut report = BulkReport { dry_run, ..Default::default() };
+
+        for input in request.create {
+            report.created.push(targets::insert_target(&txn, input, actor).await?);
+        }
+        if !request.update.is_empty() {
+            let mut current: HashMap<i32, target::Model> = target::Entity::find()
+                .filter(target::Column::Id.is_in(request.update.iter().map(|update| update.id)))
+                .all(&txn)
+                .await?
+                .into_iter()
+                .map(|target| (target.id, target))
+                .collect();
+            for update in request.update {
+                let Some(target) = current.remove(&update.id) else {
+                    return Err(sea_orm::DbErr::RecordNotFound(format!("target {}", update.id)));
+                };
+                report.updated.push(targets::update_existing(&txn, target, update.target, Action::Update, actor).await?);
+            }
+        }
+        for (ids, is_active) in [(&request.activate, true), (&request.deactivate, false)] {
+            if !ids.is_empty() {
+                target::Entity::update_many()
+                    .col_expr(target::Column::IsActive, Expr::value(is_active))
+                    .filter(target::Column::Id.is_in(ids.iter().copied()))
+                    .exec(&txn)
+                    .await?;
+                record_revisions(&txn, ids, Action::Update, actor).await?;
+            }
+        }
+        report.activated = request.activate;
+        report.deactivated = request.deactivate;
+        if !request.delete.is_empty() {
+            target::Entity::update_many()
+                .col_expr(target::Column::DeletedAt, Expr::value(chrono::Utc::now()))
+                .filter(target::Column::Id.is_in(request.delete.iter().copied()))
+                .exec(&txn)
+                .await?;
+            record_revisions(&txn, &request.delete, Action::Delete, actor).await?;
+        }
+        report.deleted = request.delete;
+
+        if dry_run {
+            txn.rollback().await?;
+        } else {
+            txn.commit().await?;
+        }
+        Ok::<_, sea_orm::DbErr>(report)
+    };
+    let report = applied.await.map_err(ApiError::database("Unable to apply target changes"))?;
+
+    if !dry_run {
+        let changed: Vec<i32> = report
+            .created
+            .iter()
+            .chain(&report.updated)
+            .map(|target| target.target.id)
+            .chain(report.activated.iter().chain(&report.deactivated).chain(&report.deleted).copied())
+            .collect();
+        state.supervisor.reload(&changed).await;
+    }
+    let inherited = targets::load_inherited(state).await?;
+    Ok(BulkReport {
+        created: report.created.into_iter().map(|target| target.resolve(&inherited)).collect(),
+        updated: report.updated.into_iter().map(|target| target.resolve(&inherited)).collect(),
+        ..report
+    })
+}
+
+// Handler to create, update, activate, deactivate and delete many targets in one transaction
+pub async fn bulk_targets(
+    State(state): State<AppState>,
+    ApiQuery(query): ApiQuery<ImportQuery>,
+    Actor(actor): Actor,
+    ApiJson(request): ApiJson<BulkRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let report = apply(&state, request, query.dry_run, &actor, &|operation, i| format!("{}[{}]", operation, i)).await?;
+    Ok(Json(report))
+}
+
+/// An imported file as bulk request, with the rows its creates and updates came from.
+#[derive(Default)]
+struct Import {
+    request: BulkRequest,
+    create_rows: Vec<String>,
+    update_rows: Vec<String>,
+}
+
+impl Import {
+    fn push(&mut self, row: String, id: Option<i32>, target: TargetInput) {
+        match id {
+            Some(id) => {
+                self.request.update.push(TargetUpdate { id, target });
+                self.update_rows.push(row);
+            }
+            None => {
+                self.request.create.push(target);
+                self.create_rows.push(row);
+            }
+        }
+    }
+}
+
+fn parse_csv(body: &str) -> Result<Import, ApiError> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(body.as_bytes());
+    let headers = reader
+        .headers()
+        .map_err(|e| ApiError::bad_request("Invalid request body", format!("Invalid CSV header: {}", e)))?
+        .clone();
+
+    let mut import = Import::default();
+    let mut fields = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| ApiError::bad_request("Invalid request body", format!("Invalid CSV: {}", e)))?;
+        // Rows are named by their line so errors can be found in the file
+        let line = format!("line {}", record.position().map_or(0, |position| position.line()));
+        let record: CsvRecord = match record.deserialize(Some(&headers)) {
+            Ok(record) => record,
+            Err(e) => {
+                fields.push(FieldError::new(line, e.to_string()));
+                continue;
+            }
+        };
+
+        let mut json = |column: &str, text: Option<String>| -> Option<serde_json::Value> {
+            serde_json::from_str(&text?)
+                .map_err(|e| fields.push(FieldError::new(format!("{}.{}", line, column), format!("invalid JSON: {}", e))))
+                .ok()
+        };
+        let probe_params = json("probe_params", record.probe_params);
+        let schedule = json("schedule", record.schedule);
+        let labels: BTreeMap<String, String> = match record.labels.map(|labels| serde_json::from_str(&labels)) {
+            Some(Ok(labels)) => labels,
+            Some(Err(e)) => {
+                fields.push(FieldError::new(format!("{}.labels", line), format!("expected a JSON object of strings: {}", e)));
+                BTreeMap::new()
+            }
+            None => BTreeMap::new(),
+        };
+
+        let input = TargetInput {
+            name: record.name,
+            host: record.host,
+            probe_type: record.probe_type,
+            probe_interval_secs: record.probe_interval_secs,
+            probe_params,
+            schedule,
+            is_active: record.is_active.unwrap_or(true),
+            group_id: record.group_id,
+            profile_id: record.profile_id,
+            // The column is authoritative, so clearing it removes the labels
+            labels: Some(labels),
+        };
+        import.push(line, record.id, input);
+    }
+
+    if fields.is_empty() {
+        Ok(import)
+    } else {
+        Err(ApiError::validation(fields))
+    }
+}
+
+fn parse_json(body: &str) -> Result<Import, ApiError> {
+    let records: Vec<JsonRecord> = serde_json::from_str(body)
+        .map_err(|e| ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "Invalid request body", e.to_string()))?;
+    let mut import = Import::default();
+    for (i, record) in records.into_iter().enumerate() {
+        import.push(format!("[{}]", i), record.id, record.target);
+    }
+    Ok(import)
+}
+
+// Handler to import targets from CSV or JSON; records with an id update that target, others are created
+pub async fn import_targets(
+    State(state): State<AppState>,
+    ApiQuery(query): ApiQuery<ImportQuery>,
+    Actor(actor): Actor,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, ApiError> {
+    let body = std::str::from_utf8(&body).map_err(|_| ApiError::bad_request("Invalid request body", "The body is not valid UTF-8"))?;
+    let content_type = headers.get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok()).unwrap_or_default();
+    let import = if content_type.starts_with("text/csv") {
+        parse_csv(body)?
+    } else if content_type.starts_with("application/json") {
+        parse_json(body)?
+    } else {
+        return Err(ApiError::new(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Invalid request body",
+            "Expected request with `Content-Type: text/csv` or `Content-Type: application/json`",
+        ));
+    };
+
+    let Import { request, create_rows, update_rows } = import;
+    let report = apply(&state, request, query.dry_run, &actor, &|operation, i| match operation {
+        "create" => create_rows[i].clone(),
+        _ => update_rows[i].clone(),
+    })
+    .await?;
+    let status = if query.dry_run || report.created.is_empty() { StatusCode::OK } else { StatusCode::CREATED };
+    Ok((status, Json(report)))
+}
+
+fn write_csv(found: Vec<TargetWithLabels>) -> Result<Vec<u8>, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for TargetWithLabels { target, labels, .. } in found {
+        let record = CsvRecord {
+            id: Some(target.id),
+            name: target.name,
+            host: target.host,
+            probe_type: target.probe_type,
+            probe_interval_secs: target.probe_interval_secs,
+            is_active: Some(target.is_active),
+            group_id: target.group_id,
+            profile_id: target.profile_id,
+            labels: (!labels.is_empty()).then(|| serde_json::json!(labels).to_string()),
+            probe_params: target.probe_params.map(|params| params.to_string()),
+            schedule: target.schedule.map(|schedule| schedule.to_string()),
+        };
+        writer.serialize(record).map_err(|e| e.to_string())?;
+    }
+    writer.into_inner().map_err(|e| e.to_string())
+}
+
+// Handler to export targets as CSV or JSON, in the format the import accepts
+pub async fn export_targets(
+    State(state): State<AppState>,
+    ApiQuery(query): ApiQuery<ExportQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    // Targets of the targets file are left out, as an import could not change them
+    let found: Vec<TargetWithLabels> = targets::find_targets(&state, &TargetQuery { group_id: query.group_id, selector: query.selector, deleted: false })
+        .await?
+        .into_iter()
+        .filter(|found| found.target.config_key.is_none())
+        .map(TargetWithLabels::into_own)
+        .collect();
+
+    match query.format.as_deref().unwrap_or("csv") {
+        "json" => Ok(Json(found).into_response()),
+        "csv" => {
+            let csv = write_csv(found).map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "Export failed", e))?;
+            Ok((
+                [(header::CONTENT_TYPE, "text/csv; charset=utf-8"), (header::CONTENT_DISPOSITION, "attachment; filename=\"targets.csv\"")],
+                csv,
+            )
+                .into_response())
+        }
+        other => Err(ApiError::bad_request("Invalid format", format!("unknown export format '{}', expected csv or json", other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migration::{Migrator, MigratorTrait};
+    use crate::models::{target_group, target_label};
+    use sea_orm::{ActiveModelTrait, ActiveValue::NotSet, Set};
+
+    fn input(name: &str, group_id: Option<i32>, labels: &[(&str, &str)]) -> TargetInput {
+        TargetInput {
+            name: name.to_string(),
+            host: "192.0.2.1".to_string(),
+            probe_type: None,
+            probe_interval_secs: None,
+            probe_params: None,
+            schedule: None,
+            is_active: true,
+            group_id,
+            profile_id: None,
+            labels: Some(labels.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()),
+        }
+    }
+
+    fn field(operation: &str, i: usize) -> String {
+        format!("{}[{}]", operation, i)
+    }
+
+    async fn database() -> DatabaseConnection {
+        let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+        db
+    }
+
+    fn error_fields(result: Result<(), ApiError>) -> Vec<String> {
+        match result {
+            Ok(()) => Vec::new(),
+            Err(e) => e.fields.into_iter().map(|field| field.field).collect(),
+        }
+    }
+
+    #[test]
+    fn labels_with_separators_survive_csv() {
+        let mut target = targets::TargetWithLabels::new(
+            target::Model {
+                id: 7,
+                name: "edge, \"1\"".to_string(),
+                host: "192.0.2.1".to_string(),
+                probe_type: Some("tcp-banner".to_string()),
+                probe_interval_secs: None,
+                probe_params: Some(serde_json::json!({ "port": 22 })),
+                schedule: None,
+                is_active: false,
+                config_key: None,
+                group_id: Some(3),
+                profile_id: None,
+                created_at: chrono::Utc::now(),
+                deleted_at: None,
+            },
+            BTreeMap::new(),
+        );
+        target.labels.insert("owner".to_string(), "ops, network=core".to_string());
+        target.labels.insert("site".to_string(), " fra ".to_string());
+        let labels = target.labels.clone();
+
+        let csv = String::from_utf8(write_csv(vec![target]).unwrap()).unwrap();
+        let Import { request, .. } = parse_csv(&csv).unwrap();
+        assert!(request.create.is_empty());
+        let TargetUpdate { id, target } = &request.update[0];
+        assert_eq!(*id, 7);
+        assert_eq!(target.name, "edge, \"1\"");
+        assert_eq!(target.probe_type.as_deref(), Some("tcp-banner"));
+        assert_eq!(target.probe_interval_secs, None);
+        assert_eq!(target.probe_params, Some(serde_json::json!({ "port": 22 })));
+        assert!(!target.is_active);
+        assert_eq!(target.group_id, Some(3));
+        assert_eq!(target.labels.as_ref(), Some(&labels));
+    }
+
+    #[test]
+    fn labels_that_are_not_a_json_object_are_rejected() {
+        let csv = "name,host,labels\nedge-1,192.0.2.1,site=fra\n";
+        let Err(e) = parse_csv(csv) else { panic!("import accepted") };
+        assert_eq!(e.fields[0].field, "line 2.labels");
+    }
+
+    #[tokio::test]
+    async fn export_imports_again_unchanged() {
+        let db = database().await;
+        let group = target_group::ActiveModel {
+            id: NotSet,
+            name: Set("europe".to_string()),
+            parent_id: Set(None),
+            default_probe_type: Set(None),
+            default_probe_interval_secs: Set(None),
+            default_probe_params: Set(None),
+            created_at: Set(chrono::Utc::now()),
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        targets::insert_target(&db, input("edge-1", Some(group.id), &[("site", "fra, ber")]), "test").await.unwrap();
+        targets::insert_target(&db, input("edge-2", None, &[]), "test").await.unwrap();
+        let managed = targets::insert_target(&db, input("edge-3", None, &[]), "test").await.unwrap();
+        let mut managed: target::ActiveModel = managed.target.into();
+        managed.config_key = Set(Some("edge-3".to_string()));
+        managed.update(&db).await.unwrap();
+
+        // What the export handler writes
+        let mut found = Vec::new();
+        for target in target::Entity::find().all(&db).await.unwrap() {
+            if target.config_key.is_none() {
+                let labels = target_label::for_target(&db, target.id).await.unwrap();
+                found.push(targets::TargetWithLabels::new(target, labels));
+            }
+        }
+        let csv = String::from_utf8(write_csv(found).unwrap()).unwrap();
+
+        let Import { request, .. } = parse_csv(&csv).unwrap();
+        assert_eq!(request.update.len(), 2);
+        assert_eq!(error_fields(validate(&db, &request, &field).await), Vec::<String>::new());
+    }
+
+    #[tokio::test]
+    async fn names_are_checked_after_the_request_is_applied() {
+        let db = database().await;
+        let a = targets::insert_target(&db, input("a", None, &[]), "test").await.unwrap().target.id;
+        let b = targets::insert_target(&db, input("b", None, &[]), "test").await.unwrap().target.id;
+        targets::insert_target(&db, input("c", None, &[]), "test").await.unwrap();
+
+        // Swapping names
+        let swap = BulkRequest {
+            update: vec![TargetUpdate { id: a, target: input("b", None, &[]) }, TargetUpdate { id: b, target: input("a", None, &[]) }],
+            ..Default::default()
+        };
+        assert_eq!(error_fields(validate(&db, &swap, &field).await), Vec::<String>::new());
+
+        // Deleting a target and creating it again
+        let recreate = BulkRequest { create: vec![input("a", None, &[])], delete: vec![a], ..Default::default() };
+        assert_eq!(error_fields(validate(&db, &recreate, &field).await), Vec::<String>::new());
+
+        // Taking the name of a target the request leaves alone, or of another entry
+        let clash = BulkRequest {
+            create: vec![input("c", None, &[]), input("d", None, &[]), input("d", None, &[])],
+            ..Default::default()
+        };
+        assert_eq!(error_fields(validate(&db, &clash, &field).await), vec!["create[0].name", "create[2].name"]);
+    }
+}
@@ -0,0 +1,124 @@
+//! The error type returned by every API handler, and extractors that report
+//! malformed requests the same way.
+//!
+//! Errors are JSON objects with a short `error` and a human-readable `message`;
+//! validation failures also list every invalid field:
+//!
+//! ```json
+//! {"error": "Validation failed", "message": "2 fields are invalid",
+//!  "fields": [{"field": "host", "message": "..."}, {"field": "probe_interval_secs", "message": "..."}]}
+//! ```
+
+use axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    extract::{FromRequest, FromRequestParts},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        FieldError { field: field.into(), message: message.into() }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ApiError {
+    #[serde(skip)]
+    pub status: StatusCode,
+    pub error: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, error: impl Into<String>, message: impl Into<String>) -> Self {
+        ApiError { status, error: error.into(), message: message.into(), fields: Vec::new() }
+    }
+
+    pub fn bad_request(error: &str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, error, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "Not found", message)
+    }
+
+    pub fn conflict(error: &str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, error, message)
+    }
+
+    /// 422 listing every invalid field of the request body.
+    pub fn validation(fields: Vec<FieldError>) -> Self {
+        let message = match fields.len() {
+            1 => format!("{}: {}", fields[0].field, fields[0].message),
+            n => format!("{} fields are invalid", n),
+        };
+        ApiError { fields, ..Self::new(StatusCode::UNPROCESSABLE_ENTITY, "Validation failed", message) }
+    }
+
+    /// Logs a database error and hides its details from the client,
+    /// e.g. `.map_err(ApiError::database("Unable to retrieve targets"))`.
+    pub fn database(message: &'static str) -> impl FnOnce(sea_orm::DbErr) -> Self {
+        move |e| {
+            eprintln!("Database error: {}: {}", message, e);
+            Self::new(StatusCode::INTERNAL_SERVER_ERROR, "Database error", message)
+        }
+    }
+
+    /// Logs an InfluxDB query error, e.g. `.map_err(ApiError::influx("Unable to retrieve probe data at this time"))`.
+    pub fn influx<E: std::fmt::Display>(message: &'static str) -> impl FnOnce(E) -> Self {
+        move |e| {
+            eprintln!("InfluxDB query failed: {}", e);
+            Self::new(StatusCode::SERVICE_UNAVAILABLE, "InfluxDB service unavailable", message)
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(self)).into_response()
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::new(rejection.status(), "Invalid request body", rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        Self::new(rejection.status(), "Invalid query string", rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        Self::new(rejection.status(), "Invalid path", rejection.body_text())
+    }
+}
+
+/// `axum::Json` that rejects malformed bodies with an `ApiError`.
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(ApiError))]
+pub struct ApiJson<T>(pub T);
+
+/// `axum::extract::Query` that rejects malformed query strings with an `ApiError`.
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(ApiError))]
+pub struct ApiQuery<T>(pub T);
+
+/// `axum::extract::Path` that rejects malformed path parameters with an `ApiError`.
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ApiError))]
+pub struct ApiPath<T>(pub T);
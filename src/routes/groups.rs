@@ -0,0 +1,245 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, NotSet, PaginatorTrait, QueryFilter, Set, TransactionTrait};
+use serde::Deserialize;
+use crate::models::target_revision::{self, Action};
+use crate::models::{target, target_group};
+use crate::routes::error::{ApiError, ApiJson, ApiPath, FieldError};
+use crate::routes::history::Actor;
+use crate::routes::targets;
+use crate::AppState;
+
+#[derive(Deserialize, Debug)]
+pub struct GroupInput {
+    pub name: String,
+    #[serde(default)]
+    pub parent_id: Option<i32>,
+    /// Defaults for members that leave these settings unset
+    #[serde(default)]
+    pub default_probe_type: Option<String>,
+    #[serde(default)]
+    pub default_probe_interval_secs: Option<i32>,
+    #[serde(default)]
+    pub default_probe_params: Option<serde_json::Value>,
+}
+
+fn group_not_found(id: i32) -> ApiError {
+    ApiError::not_found(format!("Group {} not found", id))
+}
+
+// Rejects empty names, defaults the prober could not use, and parents that do not
+// exist or would make `id` its own ancestor
+async fn validate_input(state: &AppState, id: Option<i32>, input: &GroupInput) -> Result<(), ApiError> {
+    let mut fields = Vec::new();
+    if input.name.trim().is_empty() {
+        fields.push(FieldError::new("name", "must not be empty"));
+    }
+    if let Some(Err(e)) = input.default_probe_type.as_deref().map(crate::prober::validate_probe_type) {
+        fields.push(FieldError::new("default_probe_type", e));
+    }
+    if let Some(Err(e)) = input.default_probe_interval_secs.map(crate::prober::validate_interval) {
+        fields.push(FieldError::new("default_probe_interval_secs", e));
+    }
+
+    if let Some(parent_id) = input.parent_id {
+        let groups = target_group::Entity::find()
+            .all(state.db.as_ref())
+            .await
+            .map_err(ApiError::database("Unable to check parent group"))?;
+        let mut ancestor = Some(parent_id);
+        while let Some(ancestor_id) = ancestor {
+            if Some(ancestor_id) == id {
+                fields.push(FieldError::new("parent_id", "a group cannot be nested inside itself or its subgroups"));
+                break;
+            }
+            match groups.iter().find(|group| group.id == ancestor_id) {
+                Some(group) => ancestor = group.parent_id,
+                None if ancestor_id == parent_id => {
+                    fields.push(FieldError::new("parent_id", format!("group {} does not exist", parent_id)));
+                    break;
+                }
+                None => break,
+            }
+        }
+    }
+
+    if fields.is_empty() {
+        Ok(())
+    } else {
+        Err(ApiError::validation(fields))
+    }
+}
+
+// Handler to list all groups
+pub async fn list_groups(State(state): State<AppState>) -> Result<impl IntoResponse, ApiError> {
+    let groups = target_group::Entity::find()
+        .all(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to retrieve groups"))?;
+    Ok(Json(groups))
+}
+
+// Handler to get a single group by ID
+pub async fn get_group(
+    State(state): State<AppState>,
+    ApiPath(id): ApiPath<i32>,
+) -> Result<impl IntoResponse, ApiError> {
+    let group = target_group::Entity::find_by_id(id)
+        .one(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to retrieve group"))?
+        .ok_or_else(|| group_not_found(id))?;
+    Ok(Json(group))
+}
+
+// Handler to create a new group
+pub async fn create_group(
+    State(state): State<AppState>,
+    ApiJson(input): ApiJson<GroupInput>,
+) -> Result<impl IntoResponse, ApiError> {
+    validate_input(&state, None, &input).await?;
+    let group = target_group::ActiveModel {
+        id: NotSet,
+        name: Set(input.name),
+        parent_id: Set(input.parent_id),
+        default_probe_type: Set(input.default_probe_type),
+        default_probe_interval_secs: Set(input.default_probe_interval_secs),
+        default_probe_params: Set(input.default_probe_params),
+        created_at: Set(chrono::Utc::now()),
+    };
+    let result = group
+        .insert(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to create group"))?;
+    Ok((StatusCode::CREATED, Json(result)))
+}
+
+// Handler to update an existing group
+pub async fn update_group(
+    State(state): State<AppState>,
+    ApiPath(id): ApiPath<i32>,
+    ApiJson(input): ApiJson<GroupInput>,
+) -> Result<impl IntoResponse, ApiError> {
+    let current = target_group::Entity::find_by_id(id)
+        .one(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to find group for update"))?
+        .ok_or_else(|| group_not_found(id))?;
+    validate_input(&state, Some(id), &input).await?;
+
+    // Members of the group and its subgroups may inherit the changed defaults
+    let inherited = targets::load_inherited(&state).await?;
+    let mut subtree = vec![id];
+    let mut i = 0;
+    while i < subtree.len() {
+        let parent_id = subtree[i];
+        let children: Vec<i32> = inherited
+            .groups
+            .values()
+            .filter(|group| group.parent_id == Some(parent_id) && !subtree.contains(&group.id))
+            .map(|group| group.id)
+            .collect();
+        subtree.extend(children);
+        i += 1;
+    }
+    let members = target::Entity::find()
+        .filter(target::Column::GroupId.is_in(subtree))
+        .filter(target::Column::DeletedAt.is_null())
+        .all(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to find group members"))?;
+
+    // A new default or parent can change the settings of members, which their hosts and intervals must suit
+    let mut changed = inherited.clone();
+    changed.groups.insert(
+        id,
+        target_group::Model {
+            name: input.name.clone(),
+            parent_id: input.parent_id,
+            default_probe_type: input.default_probe_type.clone(),
+            default_probe_interval_secs: input.default_probe_interval_secs,
+            default_probe_params: input.default_probe_params.clone(),
+            ..current.clone()
+        },
+    );
+    let field = if input.default_probe_type != current.default_probe_type {
+        "default_probe_type"
+    } else if input.default_probe_interval_secs != current.default_probe_interval_secs {
+        "default_probe_interval_secs"
+    } else if input.default_probe_params != current.default_probe_params {
+        "default_probe_params"
+    } else {
+        "parent_id"
+    };
+    let fields = targets::member_errors(&members, &inherited, &changed, field);
+    if !fields.is_empty() {
+        return Err(ApiError::validation(fields));
+    }
+
+    let mut group: target_group::ActiveModel = current.into();
+    group.name = Set(input.name);
+    group.parent_id = Set(input.parent_id);
+    group.default_probe_type = Set(input.default_probe_type);
+    group.default_probe_interval_secs = Set(input.default_probe_interval_secs);
+    group.default_probe_params = Set(input.default_probe_params);
+
+    let result = group
+        .update(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to update group"))?;
+
+    let members: Vec<i32> = members.into_iter().map(|target| target.id).collect();
+    state.supervisor.reload(&members).await;
+    Ok(Json(result))
+}
+
+// Handler to delete an empty group
+pub async fn delete_group(
+    State(state): State<AppState>,
+    ApiPath(id): ApiPath<i32>,
+    Actor(actor): Actor,
+) -> Result<impl IntoResponse, ApiError> {
+    let subgroups = target_group::Entity::find()
+        .filter(target_group::Column::ParentId.eq(id))
+        .count(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to check group members"))?;
+    // Deleted members leave the group with it
+    let members = target::Entity::find()
+        .filter(target::Column::GroupId.eq(id))
+        .filter(target::Column::DeletedAt.is_null())
+        .count(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to check group members"))?;
+    if subgroups > 0 || members > 0 {
+        return Err(ApiError::conflict(
+            "Group is not empty",
+            format!("Group {} still has {} subgroups and {} targets", id, subgroups, members),
+        ));
+    }
+
+    // Detach the deleted members here rather than through the foreign key, so each gets a revision
+    let deleted = async {
+        let txn = state.db.begin().await?;
+        let detached = target::Entity::find().filter(target::Column::GroupId.eq(id)).all(&txn).await?;
+        for target in detached {
+            let mut target: target::ActiveModel = target.into();
+            target.group_id = Set(None);
+            let target = target.update(&txn).await?;
+            target_revision::record(&txn, Action::Update, &actor, &target).await?;
+        }
+        let result = target_group::Entity::delete_by_id(id).exec(&txn).await?;
+        txn.commit().await?;
+        Ok::<_, sea_orm::DbErr>(result)
+    };
+    let result = deleted.await.map_err(ApiError::database("Unable to delete group"))?;
+    if result.rows_affected == 1 {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(group_not_found(id))
+    }
+}
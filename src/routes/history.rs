@@ -0,0 +1,210 @@
+//! Who changed which target when, and restoring deleted targets or earlier revisions.
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, State},
+    http::{request::Parts, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use sea_orm::{EntityTrait, TransactionTrait};
+use serde::Serialize;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use crate::live::AllowRule;
+use crate::models::target_revision::{self, Action};
+use crate::models::target;
+use crate::routes::error::{ApiError, ApiPath, FieldError};
+use crate::routes::targets::{self, TargetInput, TargetWithLabels};
+use crate::AppState;
+
+/// Reverse proxies allowed to name the user making a change, read from `TRUSTED_PROXIES`.
+pub struct TrustedProxies(Vec<AllowRule>);
+
+impl TrustedProxies {
+    pub fn from_env() -> Self {
+        let mut proxies = Vec::new();
+        let var = std::env::var("TRUSTED_PROXIES").unwrap_or_default();
+        for proxy in var.split(',').map(str::trim).filter(|proxy| !proxy.is_empty()) {
+            match AllowRule::parse(proxy) {
+                Some(rule @ AllowRule::Network(..)) => proxies.push(rule),
+                _ => eprintln!("Ignoring invalid TRUSTED_PROXIES entry '{}'", proxy),
+            }
+        }
+        TrustedProxies(proxies)
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|rule| rule.matches_ip(ip))
+    }
+}
+
+/// Who makes a change. Requests from a trusted proxy name the user in `X-Forwarded-User`
+/// or `X-Actor`; the headers of any other client are ignored and its address is recorded
+/// instead, so clients cannot claim to be someone else.
+pub struct Actor(pub String);
+
+#[async_trait]
+impl FromRequestParts<AppState> for Actor {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let Some(ConnectInfo(peer)) = parts.extensions.get::<ConnectInfo<SocketAddr>>() else {
+            return Ok(Actor("anonymous".to_string()));
+        };
+        if !state.trusted_proxies.contains(peer.ip()) {
+            return Ok(Actor(peer.ip().to_string()));
+        }
+        let actor = ["x-forwarded-user", "x-actor"]
+            .into_iter()
+            .filter_map(|header| parts.headers.get(header)?.to_str().ok())
+            .map(str::trim)
+            .find(|actor| !actor.is_empty())
+            .unwrap_or("anonymous");
+        Ok(Actor(actor.chars().take(255).collect()))
+    }
+}
+
+/// A restored target as returned by the API.
+#[derive(Serialize, Debug)]
+pub struct Restored {
+    #[serde(flatten)]
+    pub target: TargetWithLabels,
+    /// Groups and profiles of the revision that no longer exist and were left unset
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cleared: Vec<FieldError>,
+}
+
+/// A revision as returned by the API.
+#[derive(Serialize, Debug)]
+pub struct Revision {
+    #[serde(flatten)]
+    pub revision: target_revision::Model,
+    /// Fields of the snapshot that differ from the previous revision
+    pub changed: Vec<String>,
+}
+
+// Top-level fields that differ between two snapshots
+fn changed_fields(previous: &serde_json::Value, current: &serde_json::Value) -> Vec<String> {
+    let (Some(previous), Some(current)) = (previous.as_object(), current.as_object()) else {
+        return Vec::new();
+    };
+    let mut fields: Vec<String> = previous
+        .keys()
+        .chain(current.keys().filter(|key| !previous.contains_key(*key)))
+        .filter(|key| previous.get(*key) != current.get(*key))
+        .cloned()
+        .collect();
+    fields.sort();
+    fields
+}
+
+async fn find_target(state: &AppState, id: i32) -> Result<target::Model, ApiError> {
+    target::Entity::find_by_id(id)
+        .one(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to find target"))?
+        .ok_or_else(|| targets::target_not_found(id))
+}
+
+// Writes `input` back to `target`, undeleting it, and restarts its prober. A group or
+// profile deleted since the revision was recorded is left unset and reported.
+async fn restore(
+    state: &AppState,
+    target: target::Model,
+    mut input: TargetInput,
+    actor: &str,
+) -> Result<impl IntoResponse, ApiError> {
+    let id = target.id;
+    let inherited = targets::load_inherited(state).await?;
+    let mut cleared = Vec::new();
+    if let Some(group_id) = input.group_id.filter(|group_id| !inherited.groups.contains_key(group_id)) {
+        cleared.push(FieldError::new("group_id", format!("group {} no longer exists", group_id)));
+        input.group_id = None;
+    }
+    if let Some(profile_id) = input.profile_id.filter(|profile_id| !inherited.profiles.contains_key(profile_id)) {
+        cleared.push(FieldError::new("profile_id", format!("profile {} no longer exists", profile_id)));
+        input.profile_id = None;
+    }
+
+    targets::validate_input(state, Some(id), &input).await?;
+    let restored = async {
+        let txn = state.db.begin().await?;
+        let target = targets::update_existing(&txn, target, input, Action::Restore, actor).await?;
+        txn.commit().await?;
+        Ok::<_, sea_orm::DbErr>(target)
+    };
+    let result = restored.await.map_err(ApiError::database("Unable to restore target"))?;
+    state.supervisor.reload(&[id]).await;
+    Ok(Json(Restored { target: result.resolve(&inherited), cleared }))
+}
+
+// Handler to list the revisions of a target, oldest first
+pub async fn list_revisions(
+    State(state): State<AppState>,
+    ApiPath(id): ApiPath<i32>,
+) -> Result<impl IntoResponse, ApiError> {
+    find_target(&state, id).await?;
+    let revisions = target_revision::for_target(state.db.as_ref(), id)
+        .await
+        .map_err(ApiError::database("Unable to retrieve revisions"))?;
+
+    let mut previous: Option<serde_json::Value> = None;
+    let revisions: Vec<Revision> = revisions
+        .into_iter()
+        .map(|revision| {
+            let changed = previous.as_ref().map(|previous| changed_fields(previous, &revision.snapshot)).unwrap_or_default();
+            previous = Some(revision.snapshot.clone());
+            Revision { revision, changed }
+        })
+        .collect();
+    Ok(Json(revisions))
+}
+
+// Handler to bring back a deleted target with its id, settings and labels
+pub async fn restore_target(
+    State(state): State<AppState>,
+    ApiPath(id): ApiPath<i32>,
+    Actor(actor): Actor,
+) -> Result<impl IntoResponse, ApiError> {
+    let target = find_target(&state, id).await?;
+    if target.deleted_at.is_none() {
+        return Err(ApiError::conflict("Target is not deleted", format!("Target {} is not deleted", id)));
+    }
+
+    let input = TargetInput {
+        name: target.name.clone(),
+        host: target.host.clone(),
+        probe_type: target.probe_type.clone(),
+        probe_interval_secs: target.probe_interval_secs,
+        probe_params: target.probe_params.clone(),
+        schedule: target.schedule.clone(),
+        is_active: target.is_active,
+        group_id: target.group_id,
+        profile_id: target.profile_id,
+        labels: None,
+    };
+    restore(&state, target, input, &actor).await
+}
+
+// Handler to set a target back to the state recorded by one of its revisions
+pub async fn restore_revision(
+    State(state): State<AppState>,
+    ApiPath((id, revision_id)): ApiPath<(i32, i32)>,
+    Actor(actor): Actor,
+) -> Result<impl IntoResponse, ApiError> {
+    let target = find_target(&state, id).await?;
+    if target.config_key.is_some() {
+        return Err(targets::read_only(&target));
+    }
+    let revision = target_revision::Entity::find_by_id(revision_id)
+        .one(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to find revision"))?
+        .filter(|revision| revision.target_id == id)
+        .ok_or_else(|| ApiError::not_found(format!("Revision {} of target {} not found", revision_id, id)))?;
+
+    let input: TargetInput = serde_json::from_value(revision.snapshot)
+        .map_err(|e| ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "Invalid revision", e.to_string()))?;
+    restore(&state, target, input, &actor).await
+}
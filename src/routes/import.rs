@@ -0,0 +1,32 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use crate::routes::error::{ApiError, ApiQuery};
+use crate::routes::history::Actor;
+use crate::AppState;
+
+#[derive(Deserialize, Debug)]
+pub struct ImportQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+// Handler to import targets from an uploaded classic Smokeping configuration
+pub async fn import_smokeping(
+    State(state): State<AppState>,
+    ApiQuery(query): ApiQuery<ImportQuery>,
+    Actor(actor): Actor,
+    body: String,
+) -> Result<impl IntoResponse, ApiError> {
+    let report = crate::smokeping_import::import(state.db.as_ref(), &body, query.dry_run, &actor)
+        .await
+        .map_err(ApiError::database("Unable to import targets"))?;
+    let created: Vec<i32> = report.created.iter().filter_map(|target| target.id).collect();
+    state.supervisor.reload(&created).await;
+    let status = if query.dry_run || report.created.is_empty() { StatusCode::OK } else { StatusCode::CREATED };
+    Ok((status, Json(report)))
+}
@@ -0,0 +1,130 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, NotSet, QueryFilter, QueryOrder, Set};
+use serde::Deserialize;
+use crate::models::maintenance_window;
+use crate::routes::error::{ApiError, ApiJson, ApiPath, ApiQuery, FieldError};
+use crate::AppState;
+
+#[derive(Deserialize, Debug)]
+pub struct MaintenanceInput {
+    /// Defaults to now
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    /// Alternative to `ends_at`, counted from `starts_at`
+    pub duration_secs: Option<i64>,
+    /// Targets to pause; every target when omitted
+    pub target_ids: Option<Vec<i32>>,
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MaintenanceQuery {
+    pub target_id: Option<i32>,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+}
+
+// Handler to list maintenance windows, optionally those affecting a target within a range
+pub async fn list_windows(
+    State(state): State<AppState>,
+    ApiQuery(query): ApiQuery<MaintenanceQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mut select = maintenance_window::Entity::find().order_by_asc(maintenance_window::Column::StartsAt);
+    if let Some(end_time) = query.end_time {
+        select = select.filter(maintenance_window::Column::StartsAt.lt(end_time));
+    }
+    if let Some(start_time) = query.start_time {
+        select = select.filter(maintenance_window::Column::EndsAt.gt(start_time));
+    }
+
+    let windows: Vec<_> = select
+        .all(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to retrieve maintenance windows"))?
+        .into_iter()
+        .filter(|w| query.target_id.is_none_or(|id| w.covers(id)))
+        .collect();
+    Ok(Json(windows))
+}
+
+// Handler to schedule a maintenance window; probers pick it up on their next round
+pub async fn create_window(
+    State(state): State<AppState>,
+    ApiJson(input): ApiJson<MaintenanceInput>,
+) -> Result<impl IntoResponse, ApiError> {
+    let starts_at = input.starts_at.unwrap_or_else(Utc::now);
+    let ends_at = match (input.ends_at, input.duration_secs) {
+        (Some(ends_at), _) => ends_at,
+        (None, Some(secs)) if secs <= 0 => {
+            return Err(ApiError::validation(vec![FieldError::new("duration_secs", "must be a positive number of seconds")]));
+        }
+        (None, Some(secs)) => chrono::Duration::try_seconds(secs)
+            .and_then(|duration| starts_at.checked_add_signed(duration))
+            .ok_or_else(|| ApiError::validation(vec![FieldError::new("duration_secs", "the window would end too far in the future")]))?,
+        (None, None) => {
+            return Err(ApiError::validation(vec![FieldError::new("ends_at", "either ends_at or duration_secs is required")]));
+        }
+    };
+    if ends_at <= starts_at {
+        return Err(ApiError::validation(vec![FieldError::new("ends_at", "the window must end after it starts")]));
+    }
+
+    let window = maintenance_window::ActiveModel {
+        id: NotSet,
+        starts_at: Set(starts_at),
+        ends_at: Set(ends_at),
+        target_ids: Set(input.target_ids.map(|ids| serde_json::json!(ids))),
+        reason: Set(input.reason),
+        created_at: Set(Utc::now()),
+    };
+    let window = window
+        .insert(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to create maintenance window"))?;
+    state.maintenance.upsert(window.clone());
+    Ok((StatusCode::CREATED, Json(window)))
+}
+
+// Handler to end a maintenance window early; windows that have not started yet are removed,
+// ones already in progress are kept on record and end now
+pub async fn end_window(
+    State(state): State<AppState>,
+    ApiPath(id): ApiPath<i32>,
+) -> Result<impl IntoResponse, ApiError> {
+    let window = maintenance_window::Entity::find_by_id(id)
+        .one(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to find maintenance window"))?
+        .ok_or_else(|| ApiError::not_found(format!("Maintenance window {} not found", id)))?;
+
+    let now = Utc::now();
+    let result = if window.starts_at > now {
+        maintenance_window::Entity::delete_by_id(id)
+            .exec(state.db.as_ref())
+            .await
+            .map(|_| None)
+    } else if window.ends_at > now {
+        let mut active: maintenance_window::ActiveModel = window.into();
+        active.ends_at = Set(now);
+        active.update(state.db.as_ref()).await.map(Some)
+    } else {
+        Ok(Some(window))
+    };
+
+    match result.map_err(ApiError::database("Unable to end maintenance window"))? {
+        Some(window) => {
+            state.maintenance.upsert(window.clone());
+            Ok(Json(window).into_response())
+        }
+        None => {
+            state.maintenance.remove(id);
+            Ok(StatusCode::NO_CONTENT.into_response())
+        }
+    }
+}
@@ -1 +1,9 @@
+pub mod bulk;
+pub mod error;
+pub mod groups;
+pub mod history;
+pub mod import;
+pub mod maintenance;
+pub mod profiles;
+pub mod system;
 pub mod targets;
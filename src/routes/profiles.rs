@@ -0,0 +1,251 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, NotSet, PaginatorTrait, QueryFilter, Set, TransactionTrait};
+use serde::Deserialize;
+use crate::models::target_revision::{self, Action};
+use crate::models::{probe_profile, target};
+use crate::routes::error::{ApiError, ApiJson, ApiPath, FieldError};
+use crate::routes::history::Actor;
+use crate::routes::targets;
+use crate::AppState;
+
+#[derive(Deserialize, Debug)]
+pub struct ProfileInput {
+    pub name: String,
+    /// Settings left unset are inherited from the groups of the profile's targets
+    #[serde(default)]
+    pub probe_type: Option<String>,
+    #[serde(default)]
+    pub probe_interval_secs: Option<i32>,
+    #[serde(default)]
+    pub pings: Option<i32>,
+    #[serde(default)]
+    pub timeout_ms: Option<i32>,
+    #[serde(default)]
+    pub probe_params: Option<serde_json::Value>,
+    #[serde(default)]
+    pub alert_rtt_ms: Option<f64>,
+    #[serde(default)]
+    pub alert_loss_pct: Option<f64>,
+}
+
+fn profile_not_found(id: i32) -> ApiError {
+    ApiError::not_found(format!("Profile {} not found", id))
+}
+
+// Rejects duplicate or empty names and settings the prober could not use
+async fn validate_input(state: &AppState, id: Option<i32>, input: &ProfileInput) -> Result<(), ApiError> {
+    let mut fields = Vec::new();
+    if input.name.trim().is_empty() {
+        fields.push(FieldError::new("name", "must not be empty"));
+    } else {
+        let mut same_name = probe_profile::Entity::find().filter(probe_profile::Column::Name.eq(input.name.as_str()));
+        if let Some(id) = id {
+            same_name = same_name.filter(probe_profile::Column::Id.ne(id));
+        }
+        let duplicates = same_name
+            .count(state.db.as_ref())
+            .await
+            .map_err(ApiError::database("Unable to check profile name"))?;
+        if duplicates > 0 {
+            fields.push(FieldError::new("name", format!("a profile named '{}' already exists", input.name)));
+        }
+    }
+    if let Some(Err(e)) = input.probe_type.as_deref().map(crate::prober::validate_probe_type) {
+        fields.push(FieldError::new("probe_type", e));
+    }
+    if let Some(Err(e)) = input.probe_interval_secs.map(crate::prober::validate_interval) {
+        fields.push(FieldError::new("probe_interval_secs", e));
+    }
+    if input.pings.is_some_and(|pings| !(1..=1000).contains(&pings)) {
+        fields.push(FieldError::new("pings", "must be between 1 and 1000"));
+    }
+    // Checked here when the profile sets both, and for the targets it would change otherwise
+    if let (Some(pings), Some(interval_secs)) = (input.pings, input.probe_interval_secs) {
+        let pings = input.probe_params.as_ref().and_then(|params| params.get("pings")).cloned().unwrap_or(pings.into());
+        let probe_type = input.probe_type.as_deref().unwrap_or(crate::models::target::DEFAULT_PROBE_TYPE);
+        if let Err(e) = crate::prober::validate_round(probe_type, Some(&serde_json::json!({ "pings": pings })), interval_secs) {
+            fields.push(FieldError::new("pings", e));
+        }
+    }
+    if input.timeout_ms.is_some_and(|timeout_ms| timeout_ms < 1) {
+        fields.push(FieldError::new("timeout_ms", "must be positive"));
+    }
+    // Merged key by key into the parameters of the profile's targets
+    if input.probe_params.as_ref().is_some_and(|params| !params.is_object()) {
+        fields.push(FieldError::new("probe_params", "must be an object"));
+    }
+    if input.alert_rtt_ms.is_some_and(|rtt_ms| rtt_ms < 0.0) {
+        fields.push(FieldError::new("alert_rtt_ms", "must not be negative"));
+    }
+    if input.alert_loss_pct.is_some_and(|loss_pct| !(0.0..=100.0).contains(&loss_pct)) {
+        fields.push(FieldError::new("alert_loss_pct", "must be between 0 and 100"));
+    }
+
+    if fields.is_empty() {
+        Ok(())
+    } else {
+        Err(ApiError::validation(fields))
+    }
+}
+
+// Handler to list all profiles
+pub async fn list_profiles(State(state): State<AppState>) -> Result<impl IntoResponse, ApiError> {
+    let profiles = probe_profile::Entity::find()
+        .all(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to retrieve profiles"))?;
+    Ok(Json(profiles))
+}
+
+// Handler to get a single profile by ID
+pub async fn get_profile(
+    State(state): State<AppState>,
+    ApiPath(id): ApiPath<i32>,
+) -> Result<impl IntoResponse, ApiError> {
+    let profile = probe_profile::Entity::find_by_id(id)
+        .one(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to retrieve profile"))?
+        .ok_or_else(|| profile_not_found(id))?;
+    Ok(Json(profile))
+}
+
+// Handler to create a new profile
+pub async fn create_profile(
+    State(state): State<AppState>,
+    ApiJson(input): ApiJson<ProfileInput>,
+) -> Result<impl IntoResponse, ApiError> {
+    validate_input(&state, None, &input).await?;
+    let profile = probe_profile::ActiveModel {
+        id: NotSet,
+        name: Set(input.name),
+        probe_type: Set(input.probe_type),
+        probe_interval_secs: Set(input.probe_interval_secs),
+        pings: Set(input.pings),
+        timeout_ms: Set(input.timeout_ms),
+        probe_params: Set(input.probe_params),
+        alert_rtt_ms: Set(input.alert_rtt_ms),
+        alert_loss_pct: Set(input.alert_loss_pct),
+        created_at: Set(chrono::Utc::now()),
+    };
+    let result = profile
+        .insert(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to create profile"))?;
+    Ok((StatusCode::CREATED, Json(result)))
+}
+
+// Handler to update a profile and restart the probers of the targets using it
+pub async fn update_profile(
+    State(state): State<AppState>,
+    ApiPath(id): ApiPath<i32>,
+    ApiJson(input): ApiJson<ProfileInput>,
+) -> Result<impl IntoResponse, ApiError> {
+    let current = probe_profile::Entity::find_by_id(id)
+        .one(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to find profile for update"))?
+        .ok_or_else(|| profile_not_found(id))?;
+    validate_input(&state, Some(id), &input).await?;
+
+    let members = target::Entity::find()
+        .filter(target::Column::ProfileId.eq(id))
+        .filter(target::Column::DeletedAt.is_null())
+        .all(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to find profile members"))?;
+
+    // The new settings must suit the hosts and intervals of the targets that would inherit them
+    let inherited = targets::load_inherited(&state).await?;
+    let mut changed = inherited.clone();
+    changed.profiles.insert(
+        id,
+        probe_profile::Model {
+            probe_type: input.probe_type.clone(),
+            probe_interval_secs: input.probe_interval_secs,
+            pings: input.pings,
+            timeout_ms: input.timeout_ms,
+            probe_params: input.probe_params.clone(),
+            ..current.clone()
+        },
+    );
+    let field = if input.probe_type != current.probe_type {
+        "probe_type"
+    } else if input.pings != current.pings {
+        "pings"
+    } else if input.probe_interval_secs != current.probe_interval_secs {
+        "probe_interval_secs"
+    } else {
+        "probe_params"
+    };
+    let fields = targets::member_errors(&members, &inherited, &changed, field);
+    if !fields.is_empty() {
+        return Err(ApiError::validation(fields));
+    }
+
+    let mut profile: probe_profile::ActiveModel = current.into();
+    profile.name = Set(input.name);
+    profile.probe_type = Set(input.probe_type);
+    profile.probe_interval_secs = Set(input.probe_interval_secs);
+    profile.pings = Set(input.pings);
+    profile.timeout_ms = Set(input.timeout_ms);
+    profile.probe_params = Set(input.probe_params);
+    profile.alert_rtt_ms = Set(input.alert_rtt_ms);
+    profile.alert_loss_pct = Set(input.alert_loss_pct);
+
+    let result = profile
+        .update(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to update profile"))?;
+
+    let members: Vec<i32> = members.into_iter().map(|target| target.id).collect();
+    state.supervisor.reload(&members).await;
+    Ok(Json(result))
+}
+
+// Handler to delete a profile no target uses
+pub async fn delete_profile(
+    State(state): State<AppState>,
+    ApiPath(id): ApiPath<i32>,
+    Actor(actor): Actor,
+) -> Result<impl IntoResponse, ApiError> {
+    let members = target::Entity::find()
+        .filter(target::Column::ProfileId.eq(id))
+        .filter(target::Column::DeletedAt.is_null())
+        .count(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to check profile members"))?;
+    if members > 0 {
+        return Err(ApiError::conflict(
+            "Profile is in use",
+            format!("Profile {} is still used by {} targets", id, members),
+        ));
+    }
+
+    // Deleted targets lose the profile, like deleted members of a deleted group lose their group,
+    // with a revision each so the change shows up in their history
+    let deleted = async {
+        let txn = state.db.begin().await?;
+        let detached = target::Entity::find().filter(target::Column::ProfileId.eq(id)).all(&txn).await?;
+        for target in detached {
+            let mut target: target::ActiveModel = target.into();
+            target.profile_id = Set(None);
+            let target = target.update(&txn).await?;
+            target_revision::record(&txn, Action::Update, &actor, &target).await?;
+        }
+        let result = probe_profile::Entity::delete_by_id(id).exec(&txn).await?;
+        txn.commit().await?;
+        Ok::<_, sea_orm::DbErr>(result)
+    };
+    let result = deleted.await.map_err(ApiError::database("Unable to delete profile"))?;
+    if result.rows_affected == 1 {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(profile_not_found(id))
+    }
+}
@@ -0,0 +1,7 @@
+use axum::{extract::State, response::IntoResponse, Json};
+use crate::AppState;
+
+// Handler to report the startup self-check and prober health
+pub async fn get_diagnostics(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.diagnostics.report())
+}
@@ -1,12 +1,16 @@
 use axum::{
-    extract::{Path, Query, State},
+    extract::State,
     http::StatusCode,
     response::IntoResponse,
     Json,
 };
-use sea_orm::{ActiveModelTrait, EntityTrait, NotSet, Set};
+use sea_orm::{ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, NotSet, PaginatorTrait, QueryFilter, Set, TransactionTrait};
 use serde::{Deserialize, Serialize};
-use crate::models::target;
+use crate::models::target_revision::{self, Action};
+use crate::models::{maintenance_window, target, target_label};
+use crate::routes::error::{ApiError, ApiJson, ApiPath, ApiQuery, FieldError};
+use crate::routes::history::Actor;
+use std::collections::BTreeMap;
 use crate::AppState;
 use influxdb2::FromMap;
 use influxdb2::models::Query as InfluxQuery;
@@ -15,14 +19,315 @@ use influxdb2::models::Query as InfluxQuery;
 pub struct TargetInput {
     pub name: String,
     pub host: String,
-    #[serde(default = "default_probe_type")]
-    pub probe_type: String,
-    pub probe_interval_secs: i32,
+    /// Probe type and interval are inherited from the profile, then the group, when omitted
+    #[serde(default)]
+    pub probe_type: Option<String>,
+    #[serde(default)]
+    pub probe_interval_secs: Option<i32>,
+    #[serde(default)]
+    pub probe_params: Option<serde_json::Value>,
+    #[serde(default)]
+    pub schedule: Option<serde_json::Value>,
     pub is_active: bool,
+    #[serde(default)]
+    pub group_id: Option<i32>,
+    #[serde(default)]
+    pub profile_id: Option<i32>,
+    /// Replaces all labels when given; left unchanged on update when omitted
+    #[serde(default)]
+    pub labels: Option<BTreeMap<String, String>>,
 }
 
-fn default_probe_type() -> String {
-    "icmp".to_string()
+#[derive(Deserialize, Debug)]
+pub struct TargetQuery {
+    /// Only targets that are direct members of this group
+    pub group_id: Option<i32>,
+    /// Label selector, e.g. `env=prod,region!=eu`
+    pub selector: Option<String>,
+    /// Deleted targets instead of current ones
+    #[serde(default)]
+    pub deleted: bool,
+}
+
+/// A target as returned by the API, with its labels. Once resolved, the probe type and interval
+/// the target leaves unset hold the values it inherits and are listed in `inherited`.
+#[derive(Serialize, Debug)]
+pub struct TargetWithLabels {
+    #[serde(flatten)]
+    pub target: target::Model,
+    pub labels: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub inherited: Vec<&'static str>,
+}
+
+impl TargetWithLabels {
+    pub fn new(target: target::Model, labels: BTreeMap<String, String>) -> Self {
+        TargetWithLabels { target, labels, inherited: Vec::new() }
+    }
+
+    /// Fills the probe type and interval the target leaves unset with the values it is probed with.
+    pub fn resolve(mut self, inherited: &target::Inherited) -> Self {
+        let mut effective = self.target.clone();
+        inherited.apply(&mut effective);
+        if self.target.probe_type.is_none() {
+            self.target.probe_type = Some(effective.effective_probe_type().to_string());
+            self.inherited.push("probe_type");
+        }
+        if self.target.probe_interval_secs.is_none() {
+            self.target.probe_interval_secs = Some(effective.effective_probe_interval_secs());
+            self.inherited.push("probe_interval_secs");
+        }
+        self
+    }
+
+    /// Undoes [`resolve`](Self::resolve), leaving the settings the target sets itself.
+    pub fn into_own(mut self) -> Self {
+        for field in std::mem::take(&mut self.inherited) {
+            match field {
+                "probe_type" => self.target.probe_type = None,
+                "probe_interval_secs" => self.target.probe_interval_secs = None,
+                _ => {}
+            }
+        }
+        self
+    }
+}
+
+/// Loads what targets inherit, to [`resolve`](TargetWithLabels::resolve) them for a response.
+pub async fn load_inherited(state: &AppState) -> Result<target::Inherited, ApiError> {
+    target::Inherited::load(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to retrieve target groups and profiles"))
+}
+
+/// Checks `input` for target `id` (`None` when creating) and reports every invalid field at once.
+pub async fn validate_input(state: &AppState, id: Option<i32>, input: &TargetInput) -> Result<(), ApiError> {
+    let inherited = target::Inherited::load(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to check target group and profile"))?;
+    let mut fields = field_errors(input, &inherited);
+
+    // Names are unique within a group, like the entries of a Smokeping Targets section
+    let mut same_name = target::Entity::find()
+        .filter(target::Column::Name.eq(input.name.as_str()))
+        .filter(target::Column::DeletedAt.is_null());
+    same_name = match input.group_id {
+        Some(group_id) => same_name.filter(target::Column::GroupId.eq(group_id)),
+        None => same_name.filter(target::Column::GroupId.is_null()),
+    };
+    if let Some(id) = id {
+        same_name = same_name.filter(target::Column::Id.ne(id));
+    }
+    let duplicates = same_name
+        .count(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to check target name"))?;
+    if duplicates > 0 {
+        fields.push(name_taken(input));
+    }
+
+    if fields.is_empty() {
+        Ok(())
+    } else {
+        Err(ApiError::validation(fields))
+    }
+}
+
+/// Error on `name` for a target whose group already has a target of that name.
+pub fn name_taken(input: &TargetInput) -> FieldError {
+    let scope = if input.group_id.is_some() { "in this group" } else { "outside of groups" };
+    FieldError::new("name", format!("a target named '{}' already exists {}", input.name, scope))
+}
+
+/// The invalid fields of `input` apart from a taken name, checked against the groups and profiles
+/// in `inherited`, for callers that validate several targets at once.
+pub fn field_errors(input: &TargetInput, inherited: &target::Inherited) -> Vec<FieldError> {
+    let mut fields = Vec::new();
+
+    if input.name.trim().is_empty() {
+        fields.push(FieldError::new("name", "must not be empty"));
+    } else if input.name.len() > 255 {
+        fields.push(FieldError::new("name", "must be at most 255 characters"));
+    }
+    if let Some(Err(e)) = input.probe_type.as_deref().map(crate::prober::validate_probe_type) {
+        fields.push(FieldError::new("probe_type", e));
+    }
+    if let Some(Err(e)) = input.probe_interval_secs.map(crate::prober::validate_interval) {
+        fields.push(FieldError::new("probe_interval_secs", e));
+    }
+    if let Some(Err(e)) = input.schedule.as_ref().map(crate::schedule::ProbeSchedule::parse) {
+        fields.push(FieldError::new("schedule", e));
+    }
+    if let Some(Err(e)) = input.labels.as_ref().map(crate::labels::validate) {
+        fields.push(FieldError::new("labels", e));
+    }
+
+    if let Some(group_id) = input.group_id.filter(|group_id| !inherited.groups.contains_key(group_id)) {
+        fields.push(FieldError::new("group_id", format!("group {} does not exist", group_id)));
+    }
+    if let Some(profile_id) = input.profile_id.filter(|profile_id| !inherited.profiles.contains_key(profile_id)) {
+        fields.push(FieldError::new("profile_id", format!("profile {} does not exist", profile_id)));
+    }
+
+    // The host is checked against the probe type the target ends up with, which may come from its profile or groups
+    let mut effective = target::Model {
+        id: 0,
+        name: input.name.clone(),
+        host: input.host.clone(),
+        probe_type: input.probe_type.clone(),
+        probe_interval_secs: input.probe_interval_secs,
+        probe_params: input.probe_params.clone(),
+        schedule: input.schedule.clone(),
+        is_active: input.is_active,
+        config_key: None,
+        group_id: input.group_id,
+        profile_id: input.profile_id,
+        created_at: chrono::Utc::now(),
+        deleted_at: None,
+    };
+    inherited.apply(&mut effective);
+    if crate::prober::validate_probe_type(effective.effective_probe_type()).is_ok() {
+        if let Err(e) = crate::prober::validate_host(effective.effective_probe_type(), &input.host, effective.probe_params.as_ref()) {
+            fields.push(FieldError::new("host", e));
+        }
+        if let Err(e) = round_error(&effective) {
+            fields.push(FieldError::new("probe_interval_secs", e));
+        }
+    }
+
+    fields
+}
+
+// The effective settings of `target` as `prober::validate_round` checks them
+fn round_error(target: &target::Model) -> Result<(), String> {
+    crate::prober::validate_round(
+        target.effective_probe_type(),
+        target.probe_params.as_ref(),
+        target.effective_probe_interval_secs(),
+    )
+}
+
+/// Errors on `field` for every member whose host does not suit the probe type and parameters a
+/// pending group or profile change would give it, or whose round would no longer fit in its
+/// interval, `changed` being `current` with the change applied. Problems a member already had
+/// before the change are not reported.
+pub fn member_errors(
+    members: &[target::Model],
+    current: &target::Inherited,
+    changed: &target::Inherited,
+    field: &str,
+) -> Vec<FieldError> {
+    let mut fields = Vec::new();
+    for member in members {
+        let (mut before, mut after) = (member.clone(), member.clone());
+        current.apply(&mut before);
+        changed.apply(&mut after);
+        let probe_type = after.effective_probe_type();
+        if crate::prober::validate_probe_type(probe_type).is_err() {
+            continue;
+        }
+        let same_type = probe_type == before.effective_probe_type();
+        if let Err(e) = crate::prober::validate_host(probe_type, &member.host, after.probe_params.as_ref()) {
+            if !same_type || crate::prober::validate_host(probe_type, &member.host, before.probe_params.as_ref()).is_ok() {
+                fields.push(FieldError::new(
+                    field,
+                    format!("target '{}' ({}) would be probed with {}, but its host {}", member.name, member.id, probe_type, e),
+                ));
+            }
+        }
+        if let Err(e) = round_error(&after) {
+            if !same_type || round_error(&before).is_ok() {
+                fields.push(FieldError::new(field, format!("target '{}' ({}) would be probed with {}", member.name, member.id, e)));
+            }
+        }
+    }
+    fields
+}
+
+/// Inserts a validated target with its labels and records its first revision.
+pub async fn insert_target<C: ConnectionTrait>(db: &C, input: TargetInput, actor: &str) -> Result<TargetWithLabels, sea_orm::DbErr> {
+    let target = target::ActiveModel {
+        id: NotSet,
+        name: Set(input.name),
+        host: Set(input.host),
+        probe_type: Set(input.probe_type),
+        probe_interval_secs: Set(input.probe_interval_secs),
+        probe_params: Set(input.probe_params),
+        schedule: Set(input.schedule),
+        is_active: Set(input.is_active),
+        config_key: Set(None),
+        group_id: Set(input.group_id),
+        profile_id: Set(input.profile_id),
+        created_at: Set(chrono::Utc::now()),
+        deleted_at: Set(None),
+    }
+    .insert(db)
+    .await?;
+    let labels = input.labels.unwrap_or_default();
+    target_label::replace(db, target.id, &labels).await?;
+    target_revision::record(db, Action::Create, actor, &target).await?;
+    Ok(TargetWithLabels::new(target, labels))
+}
+
+/// Overwrites `target` with validated input, replacing its labels only when the input has some,
+/// and records the change as `action`. Deleted targets are restored.
+pub async fn update_existing<C: ConnectionTrait>(
+    db: &C,
+    target: target::Model,
+    input: TargetInput,
+    action: Action,
+    actor: &str,
+) -> Result<TargetWithLabels, sea_orm::DbErr> {
+    let id = target.id;
+    let mut target: target::ActiveModel = target.into();
+    target.name = Set(input.name);
+    target.host = Set(input.host);
+    target.probe_type = Set(input.probe_type);
+    target.probe_interval_secs = Set(input.probe_interval_secs);
+    target.probe_params = Set(input.probe_params);
+    target.schedule = Set(input.schedule);
+    target.is_active = Set(input.is_active);
+    target.group_id = Set(input.group_id);
+    target.profile_id = Set(input.profile_id);
+    target.deleted_at = Set(None);
+    let target = target.update(db).await?;
+    if let Some(labels) = &input.labels {
+        target_label::replace(db, id, labels).await?;
+    }
+    let labels = target_label::for_target(db, id).await?;
+    target_revision::record(db, action, actor, &target).await?;
+    Ok(TargetWithLabels::new(target, labels))
+}
+
+/// Targets matching the `group_id`, `selector` and `deleted` filters of `query`, with their labels, resolved.
+pub async fn find_targets(state: &AppState, query: &TargetQuery) -> Result<Vec<TargetWithLabels>, ApiError> {
+    let selector = query
+        .selector
+        .as_deref()
+        .map(crate::labels::Selector::parse)
+        .transpose()
+        .map_err(|e| ApiError::bad_request("Invalid selector", e))?;
+
+    let mut select = target::Entity::find().filter(if query.deleted {
+        target::Column::DeletedAt.is_not_null()
+    } else {
+        target::Column::DeletedAt.is_null()
+    });
+    if let Some(group_id) = query.group_id {
+        select = select.filter(target::Column::GroupId.eq(group_id));
+    }
+    let targets = select.all(state.db.as_ref()).await.map_err(ApiError::database("Unable to retrieve targets"))?;
+    let mut labels = target_label::all(state.db.as_ref()).await.map_err(ApiError::database("Unable to retrieve targets"))?;
+    let inherited = load_inherited(state).await?;
+
+    Ok(targets
+        .into_iter()
+        .map(|target| {
+            let labels = labels.remove(&target.id).unwrap_or_default();
+            TargetWithLabels::new(target, labels).resolve(&inherited)
+        })
+        .filter(|target| selector.as_ref().is_none_or(|selector| selector.matches(&target.labels)))
+        .collect())
 }
 
 #[derive(Deserialize)]
@@ -31,11 +336,20 @@ pub struct ProbeDataQuery {
     pub end_time: String,
 }
 
+#[derive(Deserialize)]
+pub struct PercentileQuery {
+    pub start_time: String,
+    pub end_time: String,
+    /// Comma-separated quantiles in [0, 1], defaults to 0.5,0.9,0.99
+    pub quantiles: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct InfluxProbeDataPoint {
     pub target_id: String,
     pub is_lost: String,
-    pub rtt_ms: f64,
+    pub _field: String,
+    pub _value: serde_json::Value,
     pub _time: String,
 }
 
@@ -51,21 +365,33 @@ impl FromMap for InfluxProbeDataPoint {
             }).unwrap_or("").to_string()
         };
 
-        // Helper function to safely extract numeric values
-        let get_numeric = |key: &str| -> f64 {
-            map.get(key).and_then(|v| match v {
-                Value::Double(d) => Some(d.into_inner()),
-                Value::Long(l) => Some(*l as f64),
-                Value::UnsignedLong(ul) => Some(*ul as f64),
-                _ => None,
-            }).unwrap_or(0.0)
+        // Fields differ in type per probe, so keep the value as plain JSON
+        let get_json = |key: &str| -> serde_json::Value {
+            match map.get(key) {
+                Some(Value::Double(d)) => serde_json::json!(d.into_inner()),
+                Some(Value::Long(l)) => serde_json::json!(l),
+                Some(Value::UnsignedLong(ul)) => serde_json::json!(ul),
+                Some(Value::Bool(b)) => serde_json::json!(b),
+                Some(Value::String(s)) => serde_json::json!(s),
+                _ => serde_json::Value::Null,
+            }
+        };
+
+        // Probe timestamps are written with nanosecond precision, keep all of it
+        let get_time = |key: &str| -> String {
+            match map.get(key) {
+                Some(Value::TimeRFC(t)) => t.to_rfc3339_opts(chrono::SecondsFormat::Nanos, true),
+                Some(Value::String(s)) => s.clone(),
+                _ => String::new(),
+            }
         };
 
         InfluxProbeDataPoint {
             target_id: get_string("target_id"),
             is_lost: get_string("is_lost"),
-            rtt_ms: get_numeric("rtt_ms"),
-            _time: get_string("_time"),
+            _field: get_string("_field"),
+            _value: get_json("_value"),
+            _time: get_time("_time"),
         }
     }
 }
@@ -75,166 +401,467 @@ pub struct ProbeDataPoint {
     pub time: String,
     pub rtt_ms: f64,
     pub is_lost: bool,
+    /// Any additional fields the probe recorded for this round (reply TTL, hop count, ...)
+    #[serde(flatten)]
+    pub fields: std::collections::BTreeMap<String, serde_json::Value>,
 }
 
-// Handler to list all targets
-pub async fn list_targets(State(state): State<AppState>) -> impl IntoResponse {
-    match target::Entity::find().all(state.db.as_ref()).await {
-        Ok(targets) => Json(targets).into_response(),
-        Err(e) => {
-            eprintln!("Database error listing targets: {}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
-                "error": "Database error",
-                "message": "Unable to retrieve targets"
-            }))).into_response()
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct InfluxPathDataPoint {
+    pub path_id: String,
+    pub flow: String,
+    pub is_lost: String,
+    pub _field: String,
+    pub _value: serde_json::Value,
+    pub _time: String,
+}
+
+impl FromMap for InfluxPathDataPoint {
+    fn from_genericmap(map: std::collections::BTreeMap<String, influxdb2_structmap::value::Value>) -> Self {
+        // Share the conversions of the probe data rows, only the tags differ
+        let tags = |key: &str| -> String {
+            match map.get(key) {
+                Some(influxdb2_structmap::value::Value::String(s)) => s.clone(),
+                _ => String::new(),
+            }
+        };
+        let path_id = tags("path_id");
+        let flow = tags("flow");
+        let row = InfluxProbeDataPoint::from_genericmap(map);
+
+        InfluxPathDataPoint {
+            path_id,
+            flow,
+            is_lost: row.is_lost,
+            _field: row._field,
+            _value: row._value,
+            _time: row._time,
         }
     }
 }
 
+#[derive(Serialize)]
+pub struct PathSample {
+    pub time: String,
+    pub flow: u16,
+    pub rtt_ms: f64,
+    pub is_lost: bool,
+}
+
+#[derive(Serialize)]
+pub struct PathSummary {
+    pub path_id: String,
+    pub hops: Vec<String>,
+    pub flows: Vec<u16>,
+    pub samples: usize,
+    pub lost: usize,
+    pub avg_rtt_ms: Option<f64>,
+    pub points: Vec<PathSample>,
+}
+
+// Targets owned by the targets file may only be changed there
+pub fn read_only(target: &target::Model) -> ApiError {
+    ApiError::conflict(
+        "Target is read-only",
+        format!("Target {} is managed by the targets file (key '{}')", target.id, target.config_key.as_deref().unwrap_or_default()),
+    )
+}
+
+pub fn target_not_found(id: i32) -> ApiError {
+    ApiError::not_found(format!("Target {} not found", id))
+}
+
+// Deleted targets can be looked at, but not changed or probed
+pub fn target_deleted(id: i32) -> ApiError {
+    ApiError::conflict("Target is deleted", format!("Target {} is deleted; restore it first", id))
+}
+
+// Handler to list all targets
+pub async fn list_targets(
+    State(state): State<AppState>,
+    ApiQuery(query): ApiQuery<TargetQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    Ok(Json(find_targets(&state, &query).await?))
+}
+
 // Handler to get a single target by ID
 pub async fn get_target(
     State(state): State<AppState>,
-    Path(id): Path<i32>,
-) -> impl IntoResponse {
+    ApiPath(id): ApiPath<i32>,
+) -> Result<impl IntoResponse, ApiError> {
     println!("Received get target ID: {}", id);
-    match target::Entity::find_by_id(id).one(state.db.as_ref()).await {
-        Ok(Some(target)) => Json(target).into_response(),
-        Ok(None) => (StatusCode::NOT_FOUND, "Target not found").into_response(),
-        Err(e) => {
-            eprintln!("Database error getting target {}: {}", id, e);
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
-                "error": "Database error",
-                "message": "Unable to retrieve target"
-            }))).into_response()
-        }
-    }
+    let target = target::Entity::find_by_id(id)
+        .one(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to retrieve target"))?
+        .ok_or_else(|| target_not_found(id))?;
+    let labels = target_label::for_target(state.db.as_ref(), id)
+        .await
+        .map_err(ApiError::database("Unable to retrieve target"))?;
+    Ok(Json(TargetWithLabels::new(target, labels).resolve(&load_inherited(&state).await?)))
 }
 
 // Handler to create a new target
 pub async fn create_target(
     State(state): State<AppState>,
-    Json(input): Json<TargetInput>,
-) -> impl IntoResponse {
+    Actor(actor): Actor,
+    ApiJson(input): ApiJson<TargetInput>,
+) -> Result<impl IntoResponse, ApiError> {
     println!("Received target input: {:?}", input);
-    let new_target = target::ActiveModel {
-        id: NotSet,
-        name: Set(input.name.to_owned()),
-        host: Set(input.host.to_owned()),
-        probe_type: Set(input.probe_type.to_owned()),
-        probe_interval_secs: Set(input.probe_interval_secs),
-        is_active: Set(input.is_active),
-        created_at: Set(chrono::Utc::now()),
+    validate_input(&state, None, &input).await?;
+    let created = async {
+        let txn = state.db.begin().await?;
+        let target = insert_target(&txn, input, &actor).await?;
+        txn.commit().await?;
+        Ok::<_, sea_orm::DbErr>(target)
     };
-    match new_target.insert(state.db.as_ref()).await {
-        Ok(result) => (StatusCode::CREATED, Json(result)).into_response(),
-        Err(e) => {
-            eprintln!("Database error creating target: {}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
-                "error": "Database error",
-                "message": "Unable to create target"
-            }))).into_response()
-        }
-    }
+    let result = created.await.map_err(ApiError::database("Unable to create target"))?;
+    state.supervisor.reload(&[result.target.id]).await;
+    Ok((StatusCode::CREATED, Json(result.resolve(&load_inherited(&state).await?))))
 }
 
 // Handler to update an existing target
 pub async fn update_target(
     State(state): State<AppState>,
-    Path(id): Path<i32>,
-    Json(input): Json<TargetInput>,
-) -> impl IntoResponse {
-    let mut target: target::ActiveModel = match target::Entity::find_by_id(id).one(state.db.as_ref()).await {
-        Ok(Some(target)) => target.into(),
-        Ok(None) => return (StatusCode::NOT_FOUND, "Target not found").into_response(),
-        Err(e) => {
-            eprintln!("Database error finding target {}: {}", id, e);
-            return (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
-                "error": "Database error",
-                "message": "Unable to find target for update"
-            }))).into_response();
-        }
+    ApiPath(id): ApiPath<i32>,
+    Actor(actor): Actor,
+    ApiJson(input): ApiJson<TargetInput>,
+) -> Result<impl IntoResponse, ApiError> {
+    let target = target::Entity::find_by_id(id)
+        .one(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to find target for update"))?
+        .ok_or_else(|| target_not_found(id))?;
+    if target.config_key.is_some() {
+        return Err(read_only(&target));
+    }
+    if target.deleted_at.is_some() {
+        return Err(target_deleted(id));
+    }
+    validate_input(&state, Some(id), &input).await?;
+
+    let updated = async {
+        let txn = state.db.begin().await?;
+        let target = update_existing(&txn, target, input, Action::Update, &actor).await?;
+        txn.commit().await?;
+        Ok::<_, sea_orm::DbErr>(target)
     };
+    let result = updated.await.map_err(ApiError::database("Unable to update target"))?;
+    state.supervisor.reload(&[id]).await;
+    Ok(Json(result.resolve(&load_inherited(&state).await?)))
+}
 
-    target.name = Set(input.name.to_owned());
-    target.host = Set(input.host.to_owned());
-    target.probe_type = Set(input.probe_type.to_owned());
-    target.probe_interval_secs = Set(input.probe_interval_secs);
-    target.is_active = Set(input.is_active);
+// Handler to delete a target, keeping its row, history and measurements for a later restore
+pub async fn delete_target(
+    State(state): State<AppState>,
+    ApiPath(id): ApiPath<i32>,
+    Actor(actor): Actor,
+) -> Result<impl IntoResponse, ApiError> {
+    let target = target::Entity::find_by_id(id)
+        .one(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to find target for deletion"))?
+        .ok_or_else(|| target_not_found(id))?;
+    if target.config_key.is_some() {
+        return Err(read_only(&target));
+    }
+    if target.deleted_at.is_some() {
+        return Err(target_deleted(id));
+    }
+
+    let mut target: target::ActiveModel = target.into();
+    target.deleted_at = Set(Some(chrono::Utc::now()));
+    let deleted = async {
+        let txn = state.db.begin().await?;
+        let target = target.update(&txn).await?;
+        target_revision::record(&txn, Action::Delete, &actor, &target).await?;
+        txn.commit().await
+    };
+    deleted.await.map_err(ApiError::database("Unable to delete target"))?;
+    state.supervisor.reload(&[id]).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Handler to run one probe round immediately and return its result
+pub async fn probe_target(
+    State(state): State<AppState>,
+    ApiPath(id): ApiPath<i32>,
+) -> Result<impl IntoResponse, ApiError> {
+    let target = target::Entity::find_by_id(id)
+        .one(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to find target to probe"))?
+        .ok_or_else(|| target_not_found(id))?;
+    if target.deleted_at.is_some() {
+        return Err(target_deleted(id));
+    }
+    let mut target = target;
+    target::Inherited::load(state.db.as_ref())
+        .await
+        .map_err(ApiError::database("Unable to find target to probe"))?
+        .apply(&mut target);
+    let labels = target_label::for_target(state.db.as_ref(), id)
+        .await
+        .map_err(ApiError::database("Unable to find target to probe"))?;
 
-    match target.update(state.db.as_ref()).await {
-        Ok(result) => Json(result).into_response(),
+    match crate::prober::probe_once(&target, &labels, &state.influx_client, &state.influx_config.bucket, &state.tx).await {
+        Ok(round) => Ok(Json(round)),
         Err(e) => {
-            eprintln!("Database error updating target {}: {}", id, e);
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
-                "error": "Database error",
-                "message": "Unable to update target"
-            }))).into_response()
+            eprintln!("On-demand probe of target {} failed: {}", id, e);
+            Err(ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "Probe failed", e))
         }
     }
 }
 
-// Handler to delete a target
-pub async fn delete_target(
-    State(state): State<AppState>,
-    Path(id): Path<i32>,
-) -> impl IntoResponse {
-    match target::Entity::delete_by_id(id).exec(state.db.as_ref()).await {
-        Ok(result) => {
-            if result.rows_affected == 1 {
-                (StatusCode::NO_CONTENT, "").into_response()
-            } else {
-                (StatusCode::NOT_FOUND, "Target not found").into_response()
+// Maintenance windows and schedule of a target, which rounds are flagged or left out by
+struct ExcludedRounds {
+    windows: Vec<maintenance_window::Model>,
+    schedule: Option<crate::schedule::ProbeSchedule>,
+}
+
+impl ExcludedRounds {
+    async fn load(state: &AppState, id: i32) -> Self {
+        let windows = match maintenance_window::Entity::find().all(state.db.as_ref()).await {
+            Ok(windows) => windows.into_iter().filter(|w| w.covers(id)).collect(),
+            Err(e) => {
+                eprintln!("Database error listing maintenance windows: {}", e);
+                Vec::new()
             }
-        }
-        Err(e) => {
-            eprintln!("Database error deleting target {}: {}", id, e);
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({
-                "error": "Database error",
-                "message": "Unable to delete target"
-            }))).into_response()
-        }
+        };
+        let schedule = match target::Entity::find_by_id(id).one(state.db.as_ref()).await {
+            Ok(Some(target)) => target.schedule.and_then(|s| crate::schedule::ProbeSchedule::parse(&s).ok()),
+            _ => None,
+        };
+        ExcludedRounds { windows, schedule }
+    }
+
+    fn in_maintenance(&self, time: chrono::DateTime<chrono::Utc>) -> bool {
+        self.windows.iter().any(|w| w.is_active(time))
+    }
+
+    fn out_of_schedule(&self, time: chrono::DateTime<chrono::Utc>) -> bool {
+        self.schedule.as_ref().is_some_and(|schedule| !schedule.allows(time))
+    }
+
+    // Whether the round at `time` (RFC 3339) is left out of aggregates
+    fn excludes(&self, time: &str) -> bool {
+        let Ok(time) = chrono::DateTime::parse_from_rfc3339(time) else {
+            return false;
+        };
+        let time = time.with_timezone(&chrono::Utc);
+        self.in_maintenance(time) || self.out_of_schedule(time)
     }
 }
 
 pub async fn get_probe_data(
     State(state): State<AppState>,
-    Path(id): Path<i32>,
-    Query(query): Query<ProbeDataQuery>,
-) -> impl IntoResponse {
+    ApiPath(id): ApiPath<i32>,
+    ApiQuery(query): ApiQuery<ProbeDataQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    // Fields are fetched unpivoted and merged per round below: rounds do not all carry
+    // the same fields, and the Influx client cannot parse the nulls a pivot would produce.
     let flux_query = format!(
         "from(bucket: \"{}\")
         |> range(start: {}, stop: {})
         |> filter(fn: (r) => r._measurement == \"probe_data\")
         |> filter(fn: (r) => r.target_id == \"{}\")
-        |> filter(fn: (r) => r._field == \"rtt_ms\")
-        |> keep(columns: [\"_time\", \"_value\", \"target_id\", \"is_lost\"])
-        |> rename(columns: {{\"_value\": \"rtt_ms\"}})",
+        |> filter(fn: (r) => r._field != \"rtt_sketch\")
+        |> keep(columns: [\"_time\", \"_field\", \"_value\", \"target_id\", \"is_lost\"])",
         state.influx_config.bucket,
         query.start_time,
         query.end_time,
         id
     );
 
-    // Handle InfluxDB query errors gracefully
-    let result: Vec<InfluxProbeDataPoint> = match state.influx_client.query(Some(InfluxQuery::new(flux_query))).await {
-        Ok(data) => data,
-        Err(e) => {
-            eprintln!("InfluxDB query failed: {}", e);
-            // Return empty data set instead of panicking
-            return (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({
-                "error": "InfluxDB service unavailable",
-                "message": "Unable to retrieve probe data at this time"
-            }))).into_response();
+    let result: Vec<InfluxProbeDataPoint> = state
+        .influx_client
+        .query(Some(InfluxQuery::new(flux_query)))
+        .await
+        .map_err(ApiError::influx("Unable to retrieve probe data at this time"))?;
+
+    // RFC 3339 timestamps with fixed nanosecond precision sort chronologically as strings
+    let mut rounds: std::collections::BTreeMap<String, ProbeDataPoint> = std::collections::BTreeMap::new();
+    for p in result {
+        let point = rounds.entry(p._time.clone()).or_insert_with(|| ProbeDataPoint {
+            time: p._time.clone(),
+            rtt_ms: 0.0,
+            is_lost: p.is_lost.parse().unwrap_or(false),
+            fields: Default::default(),
+        });
+        if p._field == "rtt_ms" {
+            point.rtt_ms = p._value.as_f64().unwrap_or(0.0);
+        } else {
+            point.fields.insert(p._field, p._value);
         }
+    }
+
+    // Flag rounds inside maintenance windows so charts can grey them out and skip them in loss
+    // figures, and rounds outside the target's schedule, e.g. recorded before the schedule was set
+    let excluded = ExcludedRounds::load(&state, id).await;
+    let mut data_points: Vec<ProbeDataPoint> = rounds.into_values().collect();
+    for point in data_points.iter_mut() {
+        let Ok(time) = chrono::DateTime::parse_from_rfc3339(&point.time) else {
+            continue;
+        };
+        let time = time.with_timezone(&chrono::Utc);
+        if excluded.out_of_schedule(time) {
+            point.fields.insert("out_of_schedule".to_string(), serde_json::json!(true));
+        }
+        if excluded.in_maintenance(time) {
+            point.fields.insert("maintenance".to_string(), serde_json::json!(true));
+        }
+    }
+
+    Ok(Json(data_points))
+}
+
+// Per-path latency and loss for targets probed with the `ecmp` probe type
+pub async fn get_path_data(
+    State(state): State<AppState>,
+    ApiPath(id): ApiPath<i32>,
+    ApiQuery(query): ApiQuery<ProbeDataQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let flux_query = format!(
+        "from(bucket: \"{}\")
+        |> range(start: {}, stop: {})
+        |> filter(fn: (r) => r._measurement == \"path_data\")
+        |> filter(fn: (r) => r.target_id == \"{}\")
+        |> keep(columns: [\"_time\", \"_field\", \"_value\", \"path_id\", \"flow\", \"is_lost\"])",
+        state.influx_config.bucket,
+        query.start_time,
+        query.end_time,
+        id
+    );
+
+    let result: Vec<InfluxPathDataPoint> = state
+        .influx_client
+        .query(Some(InfluxQuery::new(flux_query)))
+        .await
+        .map_err(ApiError::influx("Unable to retrieve path data at this time"))?;
+
+    // Merge the rtt_ms and hops rows of each (round, flow) before grouping by path
+    let mut samples: std::collections::BTreeMap<(String, String), (String, PathSample, String)> = std::collections::BTreeMap::new();
+    for p in result {
+        let entry = samples.entry((p._time.clone(), p.flow.clone())).or_insert_with(|| {
+            (
+                p.path_id.clone(),
+                PathSample {
+                    time: p._time.clone(),
+                    flow: p.flow.parse().unwrap_or(0),
+                    rtt_ms: 0.0,
+                    is_lost: p.is_lost.parse().unwrap_or(false),
+                },
+                String::new(),
+            )
+        });
+        match p._field.as_str() {
+            "rtt_ms" => entry.1.rtt_ms = p._value.as_f64().unwrap_or(0.0),
+            "hops" => entry.2 = p._value.as_str().unwrap_or("").to_string(),
+            _ => {}
+        }
+    }
+
+    let mut paths: std::collections::BTreeMap<String, PathSummary> = std::collections::BTreeMap::new();
+    for (path_id, sample, hops) in samples.into_values() {
+        let path = paths.entry(path_id.clone()).or_insert_with(|| PathSummary {
+            path_id,
+            hops: hops.split(',').filter(|hop| !hop.is_empty()).map(str::to_string).collect(),
+            flows: Vec::new(),
+            samples: 0,
+            lost: 0,
+            avg_rtt_ms: None,
+            points: Vec::new(),
+        });
+        if !path.flows.contains(&sample.flow) {
+            path.flows.push(sample.flow);
+        }
+        path.samples += 1;
+        if sample.is_lost {
+            path.lost += 1;
+        }
+        path.points.push(sample);
+    }
+
+    for path in paths.values_mut() {
+        path.flows.sort_unstable();
+        let answered: Vec<f64> = path.points.iter().filter(|p| !p.is_lost).map(|p| p.rtt_ms).collect();
+        if !answered.is_empty() {
+            path.avg_rtt_ms = Some(answered.iter().sum::<f64>() / answered.len() as f64);
+        }
+    }
+
+    Ok(Json(paths.into_values().collect::<Vec<_>>()))
+}
+
+// Percentiles over any time range, merged from the per-round RTT sketches
+pub async fn get_percentiles(
+    State(state): State<AppState>,
+    ApiPath(id): ApiPath<i32>,
+    ApiQuery(query): ApiQuery<PercentileQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let quantiles: Vec<f64> = match query.quantiles.as_deref() {
+        Some(list) => match list.split(',').map(|q| q.trim().parse::<f64>()).collect::<Result<Vec<_>, _>>() {
+            Ok(quantiles) if quantiles.iter().all(|q| (0.0..=1.0).contains(q)) => quantiles,
+            _ => {
+                return Err(ApiError::bad_request(
+                    "Invalid quantiles",
+                    "quantiles must be a comma-separated list of numbers between 0 and 1",
+                ));
+            }
+        },
+        None => vec![0.5, 0.9, 0.99],
     };
 
-    let data_points: Vec<ProbeDataPoint> = result.into_iter().map(|p| {
-        ProbeDataPoint {
-            time: p._time,
-            rtt_ms: p.rtt_ms,
-            is_lost: p.is_lost.parse().unwrap_or(false),
+    let flux_query = format!(
+        "from(bucket: \"{}\")
+        |> range(start: {}, stop: {})
+        |> filter(fn: (r) => r._measurement == \"probe_data\")
+        |> filter(fn: (r) => r.target_id == \"{}\")
+        |> filter(fn: (r) => r._field == \"rtt_sketch\")
+        |> keep(columns: [\"_time\", \"_field\", \"_value\", \"target_id\", \"is_lost\"])",
+        state.influx_config.bucket,
+        query.start_time,
+        query.end_time,
+        id
+    );
+
+    let result: Vec<InfluxProbeDataPoint> = state
+        .influx_client
+        .query(Some(InfluxQuery::new(flux_query)))
+        .await
+        .map_err(ApiError::influx("Unable to retrieve percentiles at this time"))?;
+
+    // Rounds get_probe_data flags as maintenance or out_of_schedule are left out, as in loss figures
+    let excluded = ExcludedRounds::load(&state, id).await;
+    let mut merged = crate::sketch::new_sketch();
+    let mut rounds = 0;
+    let mut excluded_rounds = 0;
+    for p in result {
+        if excluded.excludes(&p._time) {
+            excluded_rounds += 1;
+            continue;
+        }
+        let Some(sketch) = p._value.as_str().and_then(crate::sketch::decode) else {
+            continue;
+        };
+        if merged.merge(&sketch).is_ok() {
+            rounds += 1;
         }
-    }).collect();
+    }
+
+    let mut percentiles = serde_json::Map::new();
+    for q in quantiles {
+        let value = merged.quantile(q).ok().flatten();
+        // Round so 0.29 becomes p29 rather than p28.999999999999996
+        percentiles.insert(format!("p{}", (q * 1000.0).round() / 10.0), serde_json::json!(value));
+    }
 
-    Json(data_points).into_response()
+    Ok(Json(serde_json::json!({
+        "rounds": rounds,
+        "excluded_rounds": excluded_rounds,
+        "samples": merged.count(),
+        "min_rtt_ms": merged.min(),
+        "max_rtt_ms": merged.max(),
+        "percentiles": percentiles,
+    })))
 }
\ No newline at end of file
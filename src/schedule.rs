@@ -0,0 +1,176 @@
+//! Per-target probing schedules: outside of them a target is simply not measured.
+
+use chrono::{DateTime, Datelike, NaiveTime, Timelike, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// Schedule as stored in the `schedule` column of a target.
+#[derive(Deserialize, Debug)]
+pub struct ScheduleSpec {
+    /// IANA time zone the schedule is written in
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    /// Cron expression, 5 fields (minute resolution) or 6/7 fields with seconds
+    pub cron: Option<String>,
+    #[serde(default)]
+    pub ranges: Vec<TimeRangeSpec>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TimeRangeSpec {
+    /// Weekdays such as "mon" or "friday"; every day when empty
+    #[serde(default)]
+    pub days: Vec<String>,
+    /// "HH:MM" or "HH:MM:SS"; an end before the start runs past midnight
+    pub start: String,
+    pub end: String,
+}
+
+fn default_timezone() -> String {
+    "UTC".to_string()
+}
+
+struct TimeRange {
+    days: Vec<Weekday>,
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+pub struct ProbeSchedule {
+    timezone: Tz,
+    cron: Option<cron::Schedule>,
+    // Whether the cron expression has a seconds field, or only minute resolution
+    cron_seconds: bool,
+    ranges: Vec<TimeRange>,
+}
+
+impl ProbeSchedule {
+    pub fn parse(value: &serde_json::Value) -> Result<Self, String> {
+        let spec: ScheduleSpec = serde_json::from_value(value.clone()).map_err(|e| e.to_string())?;
+
+        let timezone = Tz::from_str(&spec.timezone).map_err(|_| format!("unknown time zone '{}'", spec.timezone))?;
+
+        let cron_seconds = spec.cron.as_ref().is_some_and(|expr| expr.split_whitespace().count() > 5);
+        let cron = spec
+            .cron
+            .map(|expr| {
+                // Standard 5-field cron has no seconds; match any second of the minute
+                let expr = if expr.split_whitespace().count() == 5 { format!("* {}", expr) } else { expr };
+                cron::Schedule::from_str(&expr).map_err(|e| format!("invalid cron expression '{}': {}", expr, e))
+            })
+            .transpose()?;
+
+        let ranges = spec
+            .ranges
+            .into_iter()
+            .map(|range| {
+                let days = range
+                    .days
+                    .iter()
+                    .map(|day| Weekday::from_str(day).map_err(|_| format!("unknown weekday '{}'", day)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(TimeRange { days, start: parse_time(&range.start)?, end: parse_time(&range.end)? })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        if cron.is_none() && ranges.is_empty() {
+            return Err("a schedule needs a cron expression or at least one time range".to_string());
+        }
+
+        Ok(ProbeSchedule { timezone, cron, cron_seconds, ranges })
+    }
+
+    /// Whether the target should be probed at `at`.
+    pub fn allows(&self, at: DateTime<Utc>) -> bool {
+        let local = at.with_timezone(&self.timezone);
+
+        if let Some(cron) = &self.cron {
+            // Rounds rarely land on a whole second, so evaluate the second they fall in, or the
+            // minute for expressions without seconds
+            let second = local.with_nanosecond(0).unwrap_or(local);
+            let start = if self.cron_seconds { second } else { second.with_second(0).unwrap_or(second) };
+            if cron.includes(start) {
+                return true;
+            }
+        }
+
+        let time = local.time();
+        let today = local.weekday();
+        self.ranges.iter().any(|range| {
+            let on = |day: Weekday| range.days.is_empty() || range.days.contains(&day);
+            if range.start <= range.end {
+                on(today) && range.start <= time && time < range.end
+            } else {
+                // Overnight range, e.g. 22:00-06:00, belongs to the day it starts on
+                (on(today) && time >= range.start) || (on(today.pred()) && time < range.end)
+            }
+        })
+    }
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(value, "%H:%M:%S"))
+        .map_err(|_| format!("invalid time '{}', expected HH:MM", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn five_field_cron_matches_the_whole_minute() {
+        // Weekdays from 08:00 to 08:59
+        let schedule = ProbeSchedule::parse(&json!({ "cron": "* 8 * * Mon-Fri" })).unwrap();
+        assert!(schedule.allows(at("2025-03-03T08:00:00Z")));
+        assert!(schedule.allows(at("2025-03-03T08:59:59.900Z")));
+        assert!(!schedule.allows(at("2025-03-03T09:00:00Z")));
+        assert!(!schedule.allows(at("2025-03-02T08:30:00Z")));
+    }
+
+    #[test]
+    fn cron_with_seconds_matches_the_second() {
+        // The first ten seconds of every minute
+        let schedule = ProbeSchedule::parse(&json!({ "cron": "0-9 * * * * *" })).unwrap();
+        assert!(schedule.allows(at("2025-03-03T08:00:00.250Z")));
+        assert!(schedule.allows(at("2025-03-03T08:00:09.999Z")));
+        assert!(!schedule.allows(at("2025-03-03T08:00:10Z")));
+
+        let schedule = ProbeSchedule::parse(&json!({ "cron": "30 * * * * *" })).unwrap();
+        assert!(schedule.allows(at("2025-03-03T08:00:30.500Z")));
+        assert!(!schedule.allows(at("2025-03-03T08:00:00Z")));
+    }
+
+    #[test]
+    fn cron_is_evaluated_in_the_schedule_time_zone() {
+        let schedule = ProbeSchedule::parse(&json!({ "timezone": "Europe/Berlin", "cron": "* 8 * * *" })).unwrap();
+        assert!(schedule.allows(at("2025-01-15T07:30:00Z")));
+        assert!(!schedule.allows(at("2025-01-15T08:30:00Z")));
+    }
+
+    #[test]
+    fn overnight_ranges_belong_to_the_day_they_start() {
+        let schedule = ProbeSchedule::parse(&json!({ "ranges": [{ "days": ["fri"], "start": "22:00", "end": "06:00" }] })).unwrap();
+        // Friday 2025-03-07
+        assert!(schedule.allows(at("2025-03-07T23:00:00Z")));
+        assert!(schedule.allows(at("2025-03-08T05:59:59Z")));
+        assert!(!schedule.allows(at("2025-03-08T06:00:00Z")));
+        assert!(!schedule.allows(at("2025-03-06T23:00:00Z")));
+        assert!(!schedule.allows(at("2025-03-07T05:00:00Z")));
+    }
+
+    #[test]
+    fn invalid_schedules_are_rejected() {
+        assert!(ProbeSchedule::parse(&json!({})).is_err());
+        assert!(ProbeSchedule::parse(&json!({ "cron": "not cron" })).is_err());
+        assert!(ProbeSchedule::parse(&json!({ "timezone": "Mars/Olympus", "cron": "* * * * *" })).is_err());
+        assert!(ProbeSchedule::parse(&json!({ "ranges": [{ "days": ["someday"], "start": "08:00", "end": "09:00" }] })).is_err());
+        assert!(ProbeSchedule::parse(&json!({ "ranges": [{ "start": "8am", "end": "09:00" }] })).is_err());
+    }
+}
@@ -0,0 +1,27 @@
+//! Mergeable RTT quantile sketches stored with every probe round.
+//!
+//! Each round's RTT samples go into a DDSketch (relative-error quantiles, default 1%)
+//! that is serialized into the `rtt_sketch` field. Sketches from any number of rounds
+//! merge losslessly, so percentiles over months only need the per-round sketches.
+
+use sketches_ddsketch::{Config, DDSketch};
+
+pub fn new_sketch() -> DDSketch {
+    DDSketch::new(Config::defaults())
+}
+
+/// Builds and serializes the sketch of one round, `None` when no sample was answered.
+pub fn encode(samples: &[f64]) -> Option<String> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mut sketch = new_sketch();
+    for sample in samples {
+        sketch.add(*sample);
+    }
+    serde_json::to_string(&sketch).ok()
+}
+
+pub fn decode(encoded: &str) -> Option<DDSketch> {
+    serde_json::from_str(encoded).ok()
+}
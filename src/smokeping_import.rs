@@ -0,0 +1,522 @@
+//! Importer for classic (Perl) Smokeping configuration files.
+//!
+//! Reads the `*** Probes ***`, `*** Targets ***` and `*** Database ***` sections,
+//! turns every target node with a `host` into a target, the nodes above it into
+//! target groups, and reports each directive that has no counterpart here instead of
+//! silently dropping it.
+
+use crate::models::{target, target_group, target_revision};
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, NotSet, QueryFilter, Set, TransactionTrait};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+
+// Smokeping's own defaults when the Database section does not set them
+const DEFAULT_STEP: i32 = 300;
+const DEFAULT_PINGS: u16 = 20;
+
+const TARGET_DIRECTIVES: &[&str] = &["menu", "host", "probe", "pings", "step", "port"];
+const PROBE_DIRECTIVES: &[&str] = &["pings", "step", "port"];
+const DATABASE_DIRECTIVES: &[&str] = &["pings", "step"];
+
+// Variables passed down the Targets tree; menu and host belong to one node
+const INHERITED: &[&str] = &["probe", "pings", "step", "port"];
+
+#[derive(Serialize, Debug, Default)]
+pub struct ImportReport {
+    /// Paths of the groups created, e.g. "Europe / Germany"
+    pub groups_created: Vec<String>,
+    pub created: Vec<ImportedTarget>,
+    pub skipped: Vec<SkippedTarget>,
+    pub unsupported: Vec<Unsupported>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImportedTarget {
+    /// Id of the created target, unset for a dry run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i32>,
+    pub name: String,
+    /// Path of the target's group
+    pub group: Option<String>,
+    pub host: String,
+    pub probe_type: String,
+    pub probe_interval_secs: i32,
+    pub probe_params: serde_json::Value,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SkippedTarget {
+    pub path: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Unsupported {
+    pub line: usize,
+    pub section: String,
+    pub directive: String,
+}
+
+struct Node {
+    depth: usize,
+    id: String,
+    vars: HashMap<String, String>,
+}
+
+#[derive(Default)]
+struct Config {
+    database: HashMap<String, String>,
+    probes: Vec<Node>,
+    targets: Vec<Node>,
+}
+
+fn parse(text: &str, report: &mut ImportReport) -> Config {
+    let mut config = Config::default();
+    let mut section = String::new();
+    let mut path: Vec<String> = Vec::new();
+
+    // Join lines continued with a trailing backslash, keeping the number of the first
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    let mut continued = false;
+    for (index, line) in text.lines().enumerate() {
+        let (line, continues) = match line.strip_suffix('\\') {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        match lines.last_mut() {
+            Some((_, last)) if continued => last.push_str(line.trim_start()),
+            _ => lines.push((index + 1, line.to_string())),
+        }
+        continued = continues;
+    }
+
+    for (number, line) in lines {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix("***").and_then(|rest| rest.strip_suffix("***")) {
+            section = name.trim().to_string();
+            path.clear();
+            if section == "Targets" {
+                config.targets.push(Node { depth: 0, id: String::new(), vars: HashMap::new() });
+            }
+            if !["Targets", "Probes", "Database"].contains(&section.as_str()) {
+                report.unsupported.push(Unsupported { line: number, section: section.clone(), directive: line.to_string() });
+            }
+            continue;
+        }
+
+        let mut unsupported = |directive: &str, path: &[String]| {
+            let section = if path.is_empty() { section.clone() } else { format!("{} {}", section, path.join("/")) };
+            report.unsupported.push(Unsupported { line: number, section, directive: directive.to_string() });
+        };
+
+        if line.starts_with('@') {
+            unsupported(line, &path);
+            continue;
+        }
+
+        let depth = line.chars().take_while(|c| *c == '+').count();
+        if depth > 0 {
+            let id = line[depth..].trim().to_string();
+            path.truncate(depth - 1);
+            path.push(id.clone());
+            let node = Node { depth, id, vars: HashMap::new() };
+            match section.as_str() {
+                "Targets" => config.targets.push(node),
+                "Probes" => config.probes.push(node),
+                _ => unsupported(line, &path),
+            }
+            continue;
+        }
+
+        let (allowed, vars) = match section.as_str() {
+            "Targets" => (TARGET_DIRECTIVES, config.targets.last_mut().map(|node| &mut node.vars)),
+            "Probes" => (PROBE_DIRECTIVES, config.probes.last_mut().map(|node| &mut node.vars)),
+            "Database" => (DATABASE_DIRECTIVES, Some(&mut config.database)),
+            // Everything in unsupported sections was reported with the section header
+            _ => continue,
+        };
+        match (line.split_once('='), vars) {
+            (Some((key, value)), Some(vars)) if allowed.contains(&key.trim()) => {
+                vars.insert(key.trim().to_string(), value.trim().to_string());
+            }
+            (Some((key, _)), _) => unsupported(key.trim(), &path),
+            (None, _) => unsupported(line, &path),
+        }
+    }
+
+    config
+}
+
+struct ProbeDef {
+    kind: String,
+    vars: HashMap<String, String>,
+}
+
+// Every probe has the module of its top-level section, e.g. `++ FPingLarge` under `+ FPing`
+fn probe_defs(probes: &[Node]) -> (Vec<String>, HashMap<String, ProbeDef>) {
+    let mut order = Vec::new();
+    let mut defs = HashMap::new();
+    let mut stack: Vec<(&str, HashMap<String, String>)> = Vec::new();
+    for node in probes {
+        stack.truncate(node.depth - 1);
+        let (kind, mut vars) = match stack.last() {
+            Some((kind, vars)) => (*kind, vars.clone()),
+            None => (node.id.as_str(), HashMap::new()),
+        };
+        vars.extend(node.vars.clone());
+        order.push(node.id.clone());
+        defs.insert(node.id.clone(), ProbeDef { kind: kind.to_string(), vars: vars.clone() });
+        stack.push((kind, vars));
+    }
+    (order, defs)
+}
+
+/// Parses `text` and turns its targets into targets with the path of their groups,
+/// without touching the database.
+fn convert(text: &str) -> (Vec<(Vec<String>, ImportedTarget)>, ImportReport) {
+    let mut report = ImportReport::default();
+    let config = parse(text, &mut report);
+    let (probe_order, probes) = probe_defs(&config.probes);
+    // Smokeping uses the first probe defined when a target names none
+    let default_probe = probe_order.first().cloned();
+
+    let mut targets = Vec::new();
+    let mut stack: Vec<(String, HashMap<String, String>)> = Vec::new();
+    for node in &config.targets {
+        stack.truncate(node.depth);
+        let mut inherited = stack.last().map(|(_, vars)| vars.clone()).unwrap_or_default();
+        inherited.extend(node.vars.iter().filter(|(key, _)| INHERITED.contains(&key.as_str())).map(|(k, v)| (k.clone(), v.clone())));
+        let menu = node.vars.get("menu").cloned().unwrap_or_else(|| node.id.clone());
+        stack.push((menu.clone(), inherited.clone()));
+
+        let Some(host) = node.vars.get("host") else { continue };
+        // The root of the Targets section is not a group, the node itself is the target
+        let group_path: Vec<String> = stack[1..stack.len() - 1].iter().map(|(menu, _)| menu.clone()).collect();
+        let path = stack.iter().skip(1).map(|(menu, _)| menu.as_str()).collect::<Vec<_>>().join(" / ");
+        let mut skip = |reason: String| report.skipped.push(SkippedTarget { path: path.clone(), reason });
+
+        if host.starts_with('/') {
+            skip("multi-host graphs are not supported".to_string());
+            continue;
+        }
+        let Some(probe_name) = inherited.get("probe").cloned().or_else(|| default_probe.clone()) else {
+            skip("no probe defined".to_string());
+            continue;
+        };
+        let Some(probe) = probes.get(&probe_name) else {
+            skip(format!("unknown probe '{}'", probe_name));
+            continue;
+        };
+
+        // Target settings win over the probe's, which win over the Database section
+        let setting = |key: &str| inherited.get(key).or_else(|| probe.vars.get(key)).or_else(|| config.database.get(key));
+        let Some(step) = setting("step").map_or(Some(DEFAULT_STEP), |step| step.parse().ok()) else {
+            skip("invalid step".to_string());
+            continue;
+        };
+        if let Err(e) = crate::prober::validate_interval(step) {
+            skip(format!("invalid step: {}", e));
+            continue;
+        }
+        let Some(pings) = setting("pings").map_or(Some(DEFAULT_PINGS), |pings| pings.parse::<u16>().ok()) else {
+            skip("invalid pings".to_string());
+            continue;
+        };
+
+        let (probe_type, probe_params) = match probe.kind.as_str() {
+            "FPing" | "FPing6" => ("icmp", json!({ "pings": pings })),
+            "TCPPing" => {
+                let Some(port) = setting("port").map_or(Some(80), |port| port.parse::<u16>().ok()) else {
+                    skip("invalid port".to_string());
+                    continue;
+                };
+                ("tcp-banner", json!({ "port": port, "connect_only": true }))
+            }
+            other => {
+                skip(format!("probe module '{}' is not supported", other));
+                continue;
+            }
+        };
+
+        // The same checks the API applies, so imported targets can be probed and edited
+        if let Err(e) = crate::prober::validate_host(probe_type, host, Some(&probe_params)) {
+            skip(format!("invalid host: {}", e));
+            continue;
+        }
+        if let Err(e) = crate::prober::validate_round(probe_type, Some(&probe_params), step) {
+            skip(format!("invalid step: {}", e));
+            continue;
+        }
+
+        let group = (!group_path.is_empty()).then(|| group_path.join(" / "));
+        targets.push((group_path, ImportedTarget {
+            id: None,
+            name: menu,
+            group,
+            host: host.clone(),
+            probe_type: probe_type.to_string(),
+            probe_interval_secs: step,
+            probe_params,
+        }));
+    }
+
+    (targets, report)
+}
+
+/// Imports the targets of a Smokeping configuration, creating a group for every
+/// Targets node above them. Existing groups with the same name and parent are reused
+/// and targets whose name already exists in their group are skipped, so importing the
+/// same file twice creates nothing new. With `dry_run` only the report is produced.
+/// Created targets are recorded as changed by `actor`.
+pub async fn import(db: &DatabaseConnection, text: &str, dry_run: bool, actor: &str) -> Result<ImportReport, sea_orm::DbErr> {
+    let (targets, mut report) = convert(text);
+
+    // A dry run makes the same changes and rolls them back
+    let txn = db.begin().await?;
+    let mut groups: HashMap<(Option<i32>, String), i32> = target_group::Entity::find()
+        .all(&txn)
+        .await?
+        .into_iter()
+        .map(|group| ((group.parent_id, group.name), group.id))
+        .collect();
+    let mut existing: HashSet<(Option<i32>, String)> = target::Entity::find()
+        .filter(target::Column::DeletedAt.is_null())
+        .all(&txn)
+        .await?
+        .into_iter()
+        .map(|target| (target.group_id, target.name))
+        .collect();
+
+    for (group_path, mut imported) in targets {
+        let mut group_id = None;
+        for (depth, name) in group_path.iter().enumerate() {
+            let key = (group_id, name.clone());
+            group_id = Some(match groups.get(&key) {
+                Some(id) => *id,
+                None => {
+                    let group = target_group::ActiveModel {
+                        id: NotSet,
+                        name: Set(name.clone()),
+                        parent_id: Set(group_id),
+                        default_probe_type: Set(None),
+                        default_probe_interval_secs: Set(None),
+                        default_probe_params: Set(None),
+                        created_at: Set(chrono::Utc::now()),
+                    }
+                    .insert(&txn)
+                    .await?;
+                    report.groups_created.push(group_path[..=depth].join(" / "));
+                    groups.insert(key, group.id);
+                    group.id
+                }
+            });
+        }
+
+        // Also catches two nodes of the file with the same menu in the same group
+        if !existing.insert((group_id, imported.name.clone())) {
+            let path = match &imported.group {
+                Some(group) => format!("{} / {}", group, imported.name),
+                None => imported.name.clone(),
+            };
+            report.skipped.push(SkippedTarget { path, reason: "a target with this name already exists in its group".to_string() });
+            continue;
+        }
+
+        let target = target::ActiveModel {
+            id: NotSet,
+            name: Set(imported.name.clone()),
+            host: Set(imported.host.clone()),
+            probe_type: Set(Some(imported.probe_type.clone())),
+            probe_interval_secs: Set(Some(imported.probe_interval_secs)),
+            probe_params: Set(Some(imported.probe_params.clone())),
+            schedule: Set(None),
+            is_active: Set(true),
+            config_key: Set(None),
+            group_id: Set(group_id),
+            profile_id: Set(None),
+            created_at: Set(chrono::Utc::now()),
+            deleted_at: Set(None),
+        }
+        .insert(&txn)
+        .await?;
+        target_revision::record(&txn, target_revision::Action::Create, actor, &target).await?;
+        imported.id = (!dry_run).then_some(target.id);
+        report.created.push(imported);
+    }
+
+    if dry_run {
+        txn.rollback().await?;
+    } else {
+        txn.commit().await?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migration::{Migrator, MigratorTrait};
+
+    const CONFIG: &str = "\
+*** Probes ***
++ FPing
+binary = /usr/sbin/fping
+pings = 10
+++ FPingFast
+pings = 5
+step = 60
++ TCPPing
+port = 22
+
+*** Database ***
+step = 300
+pings = 20
+
+*** Presentation ***
+template = /etc/smokeping/basepage.html
+
+*** Targets ***
+probe = FPing
+menu = Top
+title = Network Latency Grapher
+
++ Europe
+title = European hosts
+
+++ Germany
+probe = FPingFast
+
++++ Berlin
+host = 192.0.2.1
+
++++ Munich
+menu = München
+host = 192.0.2.2
+step = 120
+
++++ BerlinBackup
+menu = Berlin
+host = 192.0.2.4
+
+++ Ssh
+probe = TCPPing
+host = 192.0.2.3
+";
+
+    fn target<'a>(targets: &'a [(Vec<String>, ImportedTarget)], name: &str) -> &'a ImportedTarget {
+        &targets.iter().find(|(_, target)| target.name == name).unwrap().1
+    }
+
+    #[test]
+    fn nested_menus_become_group_paths() {
+        let (targets, _) = convert(CONFIG);
+        let paths: Vec<(Vec<String>, &str)> =
+            targets.iter().map(|(path, target)| (path.clone(), target.name.as_str())).collect();
+        let germany = vec!["Europe".to_string(), "Germany".to_string()];
+        assert_eq!(
+            paths,
+            vec![
+                (germany.clone(), "Berlin"),
+                (germany.clone(), "München"),
+                (germany, "Berlin"),
+                (vec!["Europe".to_string()], "Ssh"),
+            ]
+        );
+        assert_eq!(target(&targets, "München").group.as_deref(), Some("Europe / Germany"));
+    }
+
+    #[test]
+    fn settings_are_inherited_from_parents_probes_and_database() {
+        let (targets, report) = convert(CONFIG);
+        assert!(report.skipped.is_empty());
+
+        // Sub-probe settings, falling back to the probe module's
+        let berlin = target(&targets, "Berlin");
+        assert_eq!(berlin.probe_type, "icmp");
+        assert_eq!(berlin.probe_interval_secs, 60);
+        assert_eq!(berlin.probe_params, json!({ "pings": 5 }));
+
+        // A target's own step wins over its probe's
+        let munich = target(&targets, "München");
+        assert_eq!(munich.probe_interval_secs, 120);
+        assert_eq!(munich.probe_params, json!({ "pings": 5 }));
+
+        // Step from the Database section, port from the probe
+        let ssh = target(&targets, "Ssh");
+        assert_eq!(ssh.probe_type, "tcp-banner");
+        assert_eq!(ssh.probe_interval_secs, 300);
+        assert_eq!(ssh.probe_params, json!({ "port": 22, "connect_only": true }));
+    }
+
+    #[test]
+    fn unsupported_directives_and_sections_are_reported() {
+        let (_, report) = convert(CONFIG);
+        let unsupported: Vec<(usize, &str, &str)> = report
+            .unsupported
+            .iter()
+            .map(|unsupported| (unsupported.line, unsupported.section.as_str(), unsupported.directive.as_str()))
+            .collect();
+        assert_eq!(
+            unsupported,
+            vec![
+                (3, "Probes FPing", "binary"),
+                (15, "Presentation", "*** Presentation ***"),
+                (21, "Targets", "title"),
+                (24, "Targets Europe", "title"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_probes_and_invalid_settings_are_skipped() {
+        let config = "\
+*** Probes ***
++ FPing
++ DNS
+
+*** Targets ***
++ A
+host = 192.0.2.1
+probe = Curl
++ B
+host = 192.0.2.2
+probe = DNS
++ C
+host = 192.0.2.3
+step = 0
++ D
+host = /Top/A /Top/B
+";
+        let (targets, report) = convert(config);
+        assert!(targets.is_empty());
+        let skipped: Vec<(&str, &str)> =
+            report.skipped.iter().map(|skipped| (skipped.path.as_str(), skipped.reason.as_str())).collect();
+        assert_eq!(skipped[0], ("A", "unknown probe 'Curl'"));
+        assert_eq!(skipped[1], ("B", "probe module 'DNS' is not supported"));
+        assert!(skipped[2].1.starts_with("invalid step"));
+        assert_eq!(skipped[3], ("D", "multi-host graphs are not supported"));
+    }
+
+    #[tokio::test]
+    async fn duplicate_names_in_a_group_are_skipped() {
+        let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+
+        let report = import(&db, CONFIG, false, "test").await.unwrap();
+        assert_eq!(report.groups_created, vec!["Europe", "Europe / Germany"]);
+        let created: Vec<&str> = report.created.iter().map(|target| target.name.as_str()).collect();
+        assert_eq!(created, vec!["Berlin", "München", "Ssh"]);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].path, "Europe / Germany / Berlin");
+
+        // Importing the same file again creates nothing
+        let report = import(&db, CONFIG, false, "test").await.unwrap();
+        assert!(report.groups_created.is_empty());
+        assert!(report.created.is_empty());
+        assert_eq!(report.skipped.len(), 4);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::diagnostics::Diagnostics;
+use crate::maintenance::Maintenance;
+use crate::models::{target, target_label};
+use influxdb2::Client;
+use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{broadcast, Mutex};
+use tokio::task::AbortHandle;
+
+/// Runs one prober per active target and restarts probers whose target, group or
+/// profile settings changed.
+pub struct Supervisor {
+    db: Arc<DatabaseConnection>,
+    client: Client,
+    bucket: String,
+    tx: broadcast::Sender<String>,
+    maintenance: Arc<Maintenance>,
+    diagnostics: Arc<Diagnostics>,
+    // Held across a whole restart so that concurrent reloads cannot start outdated probers
+    probers: Mutex<HashMap<i32, AbortHandle>>,
+}
+
+impl Supervisor {
+    pub fn new(
+        db: Arc<DatabaseConnection>,
+        client: Client,
+        bucket: String,
+        tx: broadcast::Sender<String>,
+        maintenance: Arc<Maintenance>,
+        diagnostics: Arc<Diagnostics>,
+    ) -> Self {
+        Supervisor { db, client, bucket, tx, maintenance, diagnostics, probers: Mutex::new(HashMap::new()) }
+    }
+
+    /// Starts the probers of all active targets.
+    pub async fn start_all(&self) -> Result<(), DbErr> {
+        self.restart(None).await
+    }
+
+    /// Restarts the probers of `target_ids` with their current settings; targets that were
+    /// deleted or deactivated are only stopped. Failures are logged, since the change that
+    /// called for the reload is already saved.
+    pub async fn reload(&self, target_ids: &[i32]) {
+        if target_ids.is_empty() {
+            return;
+        }
+        if let Err(e) = self.restart(Some(target_ids)).await {
+            eprintln!("Failed to reload probers of targets {:?}: {:?}", target_ids, e);
+        }
+    }
+
+    async fn restart(&self, target_ids: Option<&[i32]>) -> Result<(), DbErr> {
+        let mut probers = self.probers.lock().await;
+
+        let mut select = target::Entity::find()
+            .filter(target::Column::IsActive.eq(true))
+            .filter(target::Column::DeletedAt.is_null());
+        if let Some(target_ids) = target_ids {
+            select = select.filter(target::Column::Id.is_in(target_ids.iter().copied()));
+        }
+        let targets = select.all(self.db.as_ref()).await?;
+        let inherited = target::Inherited::load(self.db.as_ref()).await?;
+        let mut labels = target_label::all(self.db.as_ref()).await?;
+
+        let stopped: Vec<i32> = match target_ids {
+            Some(target_ids) => target_ids.to_vec(),
+            None => probers.keys().copied().collect(),
+        };
+        for target_id in stopped {
+            if let Some(prober) = probers.remove(&target_id) {
+                prober.abort();
+                self.diagnostics.prober_stopped(target_id);
+            }
+        }
+
+        for mut target in targets {
+            inherited.apply(&mut target);
+            let target_id = target.id;
+            let target_labels = labels.remove(&target_id).unwrap_or_default();
+            self.diagnostics.prober_started();
+            let prober = tokio::spawn(crate::prober::run_prober(
+                target,
+                target_labels,
+                self.client.clone(),
+                self.bucket.clone(),
+                self.tx.clone(),
+                self.maintenance.clone(),
+                self.diagnostics.clone(),
+            ));
+            probers.insert(target_id, prober.abort_handle());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+//! Declarative targets file, reconciled into the `targets` table at startup.
+//!
+//! Every entry has a stable `key`. Entries are created or updated to match the file,
+//! and file-managed targets that disappeared from it are deactivated rather than
+//! deleted so their history stays reachable. Targets created through the API are
+//! never touched.
+
+use crate::models::target_revision::{self, Action};
+use crate::models::{target, target_label};
+use crate::schedule::ProbeSchedule;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, NotSet, QueryFilter, Set, TransactionTrait};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TargetsFile {
+    #[serde(default)]
+    pub targets: Vec<TargetSpec>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TargetSpec {
+    /// Identifies the target across renames and host changes
+    pub key: String,
+    /// Defaults to the key
+    pub name: Option<String>,
+    pub host: String,
+    #[serde(default = "default_probe_type")]
+    pub probe_type: String,
+    pub probe_interval_secs: i32,
+    pub probe_params: Option<serde_json::Value>,
+    pub schedule: Option<serde_json::Value>,
+    #[serde(default = "default_active")]
+    pub is_active: bool,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+}
+
+// Recorded as the author of the revisions a sync creates
+const ACTOR: &str = "targets-file";
+
+fn default_probe_type() -> String {
+    "icmp".to_string()
+}
+
+fn default_active() -> bool {
+    true
+}
+
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    pub created: usize,
+    pub updated: usize,
+    pub deactivated: usize,
+}
+
+/// Reads a `.toml`, `.yaml` or `.yml` targets file and checks it for consistency.
+pub fn load(path: &Path) -> Result<TargetsFile, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read targets file '{}': {}", path.display(), e))?;
+
+    let file: TargetsFile = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => toml::from_str(&contents).map_err(|e| e.to_string()),
+        Some("yaml") | Some("yml") => serde_yaml::from_str(&contents).map_err(|e| e.to_string()),
+        _ => Err("expected a .toml, .yaml or .yml file".to_string()),
+    }
+    .map_err(|e| format!("Invalid targets file '{}': {}", path.display(), e))?;
+
+    let mut keys = HashSet::new();
+    for spec in &file.targets {
+        if !keys.insert(spec.key.as_str()) {
+            return Err(format!("Duplicate key '{}' in targets file", spec.key));
+        }
+        crate::prober::validate_probe_type(&spec.probe_type).map_err(|e| format!("Invalid probe_type of target '{}': {}", spec.key, e))?;
+        crate::prober::validate_interval(spec.probe_interval_secs)
+            .map_err(|e| format!("Invalid probe_interval_secs of target '{}': {}", spec.key, e))?;
+        crate::prober::validate_host(&spec.probe_type, &spec.host, spec.probe_params.as_ref()).map_err(|e| format!("Invalid host of target '{}': {}", spec.key, e))?;
+        crate::prober::validate_round(&spec.probe_type, spec.probe_params.as_ref(), spec.probe_interval_secs)
+            .map_err(|e| format!("Invalid probe_interval_secs of target '{}': {}", spec.key, e))?;
+        crate::labels::validate(&spec.labels).map_err(|e| format!("Invalid labels of target '{}': {}", spec.key, e))?;
+        if let Some(schedule) = &spec.schedule {
+            ProbeSchedule::parse(schedule).map_err(|e| format!("Invalid schedule of target '{}': {}", spec.key, e))?;
+        }
+    }
+
+    Ok(file)
+}
+
+/// Makes the file-managed targets in the database match `file`, in one transaction.
+pub async fn sync(db: &DatabaseConnection, file: TargetsFile) -> Result<SyncReport, sea_orm::DbErr> {
+    let txn = db.begin().await?;
+    let mut report = SyncReport::default();
+
+    let mut labels = target_label::all(&txn).await?;
+    let mut existing: HashMap<String, target::Model> = target::Entity::find()
+        .filter(target::Column::ConfigKey.is_not_null())
+        .all(&txn)
+        .await?
+        .into_iter()
+        .filter_map(|target| Some((target.config_key.clone()?, target)))
+        .collect();
+
+    for spec in file.targets {
+        let name = spec.name.unwrap_or_else(|| spec.key.clone());
+        match existing.remove(&spec.key) {
+            Some(current) => {
+                let unchanged = current.name == name
+                    && current.host == spec.host
+                    && current.probe_type.as_ref() == Some(&spec.probe_type)
+                    && current.probe_interval_secs == Some(spec.probe_interval_secs)
+                    && current.probe_params == spec.probe_params
+                    && current.schedule == spec.schedule
+                    && current.is_active == spec.is_active
+                    && labels.remove(&current.id).unwrap_or_default() == spec.labels;
+                if unchanged {
+                    continue;
+                }
+                target_label::replace(&txn, current.id, &spec.labels).await?;
+                let mut target: target::ActiveModel = current.into();
+                target.name = Set(name);
+                target.host = Set(spec.host);
+                target.probe_type = Set(Some(spec.probe_type));
+                target.probe_interval_secs = Set(Some(spec.probe_interval_secs));
+                target.probe_params = Set(spec.probe_params);
+                target.schedule = Set(spec.schedule);
+                target.is_active = Set(spec.is_active);
+                let target = target.update(&txn).await?;
+                target_revision::record(&txn, Action::Update, ACTOR, &target).await?;
+                report.updated += 1;
+            }
+            None => {
+                let target = target::ActiveModel {
+                    id: NotSet,
+                    name: Set(name),
+                    host: Set(spec.host),
+                    probe_type: Set(Some(spec.probe_type)),
+                    probe_interval_secs: Set(Some(spec.probe_interval_secs)),
+                    probe_params: Set(spec.probe_params),
+                    schedule: Set(spec.schedule),
+                    is_active: Set(spec.is_active),
+                    config_key: Set(Some(spec.key)),
+                    group_id: Set(None),
+                    profile_id: Set(None),
+                    created_at: Set(chrono::Utc::now()),
+                    deleted_at: Set(None),
+                }
+                .insert(&txn)
+                .await?;
+                target_label::replace(&txn, target.id, &spec.labels).await?;
+                target_revision::record(&txn, Action::Create, ACTOR, &target).await?;
+                report.created += 1;
+            }
+        }
+    }
+
+    // Whatever is left was removed from the file; it is deactivated and handed over to the
+    // API, which can then edit or delete it
+    for (_, removed) in existing {
+        let mut target: target::ActiveModel = removed.into();
+        target.is_active = Set(false);
+        target.config_key = Set(None);
+        let target = target.update(&txn).await?;
+        target_revision::record(&txn, Action::Update, ACTOR, &target).await?;
+        report.deactivated += 1;
+    }
+
+    txn.commit().await?;
+    Ok(report)
+}